@@ -1,6 +1,54 @@
+#[cfg(feature = "extract")]
 use cmake::Config;
 
+/// Suite embedded into builds without the `extract` feature.
+#[cfg(not(feature = "extract"))]
+const BUNDLED_SUITE: &str = "suite/conformance.json";
+
 fn main() {
+    #[cfg(feature = "extract")]
+    build_conformance();
+
+    #[cfg(not(feature = "extract"))]
+    check_bundled_suite();
+
+    // Proto, vendored in include so that builds without the `extract` feature
+    // don't need the protobuf submodule
+    let mut codegen = protobuf_codegen::Codegen::new();
+    // builds without the C++ toolchain shouldn't need protoc either
+    #[cfg(feature = "extract")]
+    codegen.protoc();
+    #[cfg(not(feature = "extract"))]
+    codegen.pure();
+    codegen
+        .include("include")
+        .input("include/conformance.proto")
+        .input("include/test_messages_proto3.proto")
+        .input("include/test_messages_proto2.proto")
+        .cargo_out_dir("conformance")
+        .run_from_script();
+}
+
+/// Fails the build when the bundled suite has no cases, since the binary
+/// would have nothing to run without `--suite`.
+#[cfg(not(feature = "extract"))]
+fn check_bundled_suite() {
+    println!("cargo:rerun-if-changed={}", BUNDLED_SUITE);
+    let contents = std::fs::read_to_string(BUNDLED_SUITE)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", BUNDLED_SUITE, e));
+    let suite: serde_json::Value = serde_json::from_str(&contents)
+        .unwrap_or_else(|e| panic!("failed to parse {}: {}", BUNDLED_SUITE, e));
+    let cases = suite["cases"].as_array().map_or(0, Vec::len);
+    if cases == 0 {
+        panic!(
+            "{} has no cases, regenerate it with `conform export-suite` from a build with the `extract` feature",
+            BUNDLED_SUITE
+        );
+    }
+}
+
+#[cfg(feature = "extract")]
+fn build_conformance() {
     let cfg = Config::new("third_party/protobuf")
         .define("protobuf_BUILD_TESTS", "OFF")
         .define("protobuf_WITH_ZLIB", "OFF")
@@ -60,15 +108,4 @@ fn main() {
     println!("cargo:rustc-link-lib=conformance");
 
     println!("cargo:warning={}", &out_dir.to_string());
-
-    // Proto
-    protobuf_codegen::Codegen::new()
-        .protoc()
-        .include("third_party/protobuf/conformance")
-        .include("include")
-        .input("third_party/protobuf/conformance/conformance.proto")
-        .input("include/test_messages_proto3.proto")
-        .input("include/test_messages_proto2.proto")
-        .cargo_out_dir("conformance")
-        .run_from_script();
 }
@@ -1,9 +1,9 @@
 use std::fmt::{self, Formatter};
 
 use super::conformance::{self, conformance_response::Result};
-use super::differ;
+use super::equality::{self, Difference, UnknownFields};
 use super::message::TestMessage;
-use super::validator::Validator;
+use super::validator;
 use crate::test_case::{AssertBy, TestCase};
 use anyhow::{anyhow, Context, Ok};
 use protobuf::{text_format, Message};
@@ -66,8 +66,13 @@ impl ToString for Result {
 pub struct Outcome {
     pub case: Case,
     pub diagnostics: Vec<String>,
+    /// Fields that differ from the equivalent message, when asserted by equivalence.
+    pub differences: Vec<Difference>,
 }
 
+/// Differing fields listed in the diagnostics before the rest is summarized.
+const MAX_DIFFERENCES: usize = 20;
+
 #[derive(Default, PartialEq, Eq)]
 pub enum Case {
     Skipped,
@@ -87,6 +92,18 @@ impl From<bool> for Case {
 }
 
 impl Outcome {
+    /// Outcome of a case that could not be asserted, such as when the
+    /// program didn't answer.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let mut outcome = Outcome::default().set_case(Case::Failed);
+        outcome.add_diagnostic(format!("{:#}", error));
+        outcome
+    }
+    /// Outcome of a case that was not sent to the program.
+    pub fn not_run() -> Self {
+        Outcome::default().set_case(Case::Skipped)
+    }
+
     fn set_case(mut self, case: Case) -> Self {
         self.case = case;
         self
@@ -112,12 +129,23 @@ impl fmt::Display for Outcome {
     }
 }
 
+fn describe_differences(differences: &[Difference]) -> String {
+    let mut lines: Vec<String> = differences
+        .iter()
+        .take(MAX_DIFFERENCES)
+        .map(|d| d.to_string())
+        .collect();
+    if differences.len() > MAX_DIFFERENCES {
+        lines.push(format!(
+            "... and {} more",
+            differences.len() - MAX_DIFFERENCES
+        ));
+    }
+    format!("differing fields:\n{}", lines.join("\n"))
+}
+
 pub fn assert(case: &TestCase, res: &Vec<u8>) -> Outcome {
-    assert_inner(case, res).unwrap_or_else(|e| {
-        let mut outcome = Outcome::default().set_case(Case::Failed);
-        outcome.add_diagnostic(e.to_string());
-        outcome
-    })
+    assert_inner(case, res).unwrap_or_else(|e| Outcome::from_error(&e))
 }
 
 pub fn assert_inner(case: &TestCase, res: &Vec<u8>) -> anyhow::Result<Outcome> {
@@ -157,19 +185,33 @@ pub fn assert_inner(case: &TestCase, res: &Vec<u8>) -> anyhow::Result<Outcome> {
                     || format!("{}\nfailed to parse the message from json payload", outcome),
                 )?,
             ),
-            Result::TextPayload(_) => todo!("text is not supported yet."),
-            Result::JspbPayload(_) => todo!("jspb is not supported."),
+            Result::TextPayload(ref payload) => Some(
+                TestMessage::from_text(&request.message_type, payload).with_context(|| {
+                    format!("{}\nfailed to parse the message from text payload", outcome)
+                })?,
+            ),
+            Result::JspbPayload(_) => {
+                outcome.add_diagnostic(
+                    "jspb is not supported, the case should have been skipped.".to_string(),
+                );
+                return Ok(outcome.set_case(Case::Failed));
+            }
             _ => None,
         };
 
         let equivalent_message =
             TestMessage::from_protobuf_payload(&request.message_type, equivalent)?;
 
-        let (differs, difference) =
-            differ::diff(&message.unwrap().to_text(), &equivalent_message.to_text());
-        outcome.add_diagnostic(difference);
-
-        let passed = !differs;
+        let message = message.unwrap();
+        outcome.differences = equality::differences(
+            message.as_dyn(),
+            equivalent_message.as_dyn(),
+            UnknownFields::Compare,
+        );
+        let passed = outcome.differences.is_empty();
+        if !passed {
+            outcome.add_diagnostic(describe_differences(&outcome.differences));
+        }
 
         return Ok(outcome.set_case(passed.into()));
     } else if AssertBy::ParseError == case.assert_by {
@@ -184,7 +226,7 @@ pub fn assert_inner(case: &TestCase, res: &Vec<u8>) -> anyhow::Result<Outcome> {
         }
         let passed = result.is_serialize_error();
         return Ok(outcome.set_case(passed.into()));
-    } else if AssertBy::Validator == case.assert_by {
+    } else if let AssertBy::Validator(checks) = &case.assert_by {
         let raw_json = if let Result::JsonPayload(json) = result {
             json
         } else {
@@ -196,10 +238,12 @@ pub fn assert_inner(case: &TestCase, res: &Vec<u8>) -> anyhow::Result<Outcome> {
             return Ok(outcome.set_case(Case::Failed));
         }
 
-        let validator: Validator = case.into();
         let value = serde_json::from_str::<serde_json::Value>(raw_json.as_str())
             .with_context(|| format!("{}\nfailed to parse the response json", outcome))?;
-        let validation_result = validator(value);
+        let validation_result = match checks {
+            Some(checks) => validator::check_all(checks, &value),
+            None => Err(anyhow!("unimplemented validator")),
+        };
         let passed = !validation_result.is_err();
         if !passed {
             outcome.add_diagnostic(format!(
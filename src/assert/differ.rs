@@ -1,22 +1,152 @@
-pub fn diff(left: &str, right: &str) -> (bool, String) {
-    let mut differs = false;
-    let mut diff = String::new();
-    for d in diff::lines(left, right) {
-        match d {
-            diff::Result::Both(l, _) => {
-                diff.push_str(&format!("{}\n", l));
-                differs = false;
-            }
-            diff::Result::Left(l) => {
-                diff.push_str(&format!("- {}\n", l));
-                differs = true;
-            }
-            diff::Result::Right(r) => {
-                diff.push_str(&format!("+ {}\n", r));
-                differs = true;
+/// Lines of unchanged text kept around each change by default.
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// Line diff of `left` against `right` in unified format, keeping `context`
+/// unchanged lines around every change. Returns whether the texts differ
+/// along with the hunks; identical texts give an empty diff.
+pub fn diff(left: &str, right: &str, context: usize) -> (bool, String) {
+    let lines: Vec<diff::Result<&str>> = diff::lines(left, right);
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, diff::Result::Both(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let Some(&first) = changes.first() else {
+        return (false, String::new());
+    };
+
+    // group changes that are close enough to share their context
+    let mut hunks = vec![(first, first)];
+    for &change in &changes[1..] {
+        let last = hunks.last_mut().unwrap();
+        if change - last.1 > 2 * context + 1 {
+            hunks.push((change, change));
+        } else {
+            last.1 = change;
+        }
+    }
+
+    let mut out = String::new();
+    for (first, last) in hunks {
+        let start = first.saturating_sub(context);
+        let end = last.saturating_add(context).saturating_add(1).min(lines.len());
+        out.push_str(&header(&lines, start, end));
+        for line in &lines[start..end] {
+            match line {
+                diff::Result::Both(l, _) => out.push_str(&format!(" {}\n", l)),
+                diff::Result::Left(l) => out.push_str(&format!("-{}\n", l)),
+                diff::Result::Right(r) => out.push_str(&format!("+{}\n", r)),
             }
         }
     }
 
-    (differs, diff)
+    (true, out)
+}
+
+/// `@@ -l,s +l,s @@` header of the hunk covering `lines[start..end]`.
+fn header(lines: &[diff::Result<&str>], start: usize, end: usize) -> String {
+    let count = |lines: &[diff::Result<&str>]| {
+        lines.iter().fold((0, 0), |(left, right), line| match line {
+            diff::Result::Both(..) => (left + 1, right + 1),
+            diff::Result::Left(_) => (left + 1, right),
+            diff::Result::Right(_) => (left, right + 1),
+        })
+    };
+    let (left_before, right_before) = count(&lines[..start]);
+    let (left_len, right_len) = count(&lines[start..end]);
+    // an empty range is numbered by the line before it
+    let first = |before: usize, len: usize| if len == 0 { before } else { before + 1 };
+    format!(
+        "@@ -{},{} +{},{} @@\n",
+        first(left_before, left_len),
+        left_len,
+        first(right_before, right_len),
+        right_len
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+
+    fn lines(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("line {}", i)).collect()
+    }
+
+    fn changed(at: &[usize], n: usize) -> String {
+        let mut lines = lines(n);
+        for &i in at {
+            lines[i] = format!("changed {}", i + 1);
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn identical() {
+        let text = lines(10).join("\n");
+        assert_eq!(diff(&text, &text, 3), (false, String::new()));
+    }
+
+    #[test]
+    fn mismatch_at_start() {
+        let (differs, out) = diff(&changed(&[0], 10), &lines(10).join("\n"), 2);
+        assert!(differs);
+        assert_eq!(
+            out,
+            "@@ -1,3 +1,3 @@\n-changed 1\n+line 1\n line 2\n line 3\n"
+        );
+    }
+
+    #[test]
+    fn mismatch_in_middle() {
+        let (differs, out) = diff(&changed(&[4], 10), &lines(10).join("\n"), 1);
+        assert!(differs);
+        assert_eq!(
+            out,
+            "@@ -4,3 +4,3 @@\n line 4\n-changed 5\n+line 5\n line 6\n"
+        );
+    }
+
+    #[test]
+    fn mismatch_at_end() {
+        let (differs, out) = diff(&changed(&[9], 10), &lines(10).join("\n"), 2);
+        assert!(differs);
+        assert_eq!(
+            out,
+            "@@ -8,3 +8,3 @@\n line 8\n line 9\n-changed 10\n+line 10\n"
+        );
+    }
+
+    #[test]
+    fn mismatch_followed_by_identical_lines() {
+        let (differs, _) = diff(&changed(&[1], 50), &lines(50).join("\n"), 3);
+        assert!(differs);
+    }
+
+    #[test]
+    fn separate_hunks() {
+        let (_, out) = diff(&changed(&[1, 15], 20), &lines(20).join("\n"), 1);
+        assert_eq!(
+            out,
+            "@@ -1,3 +1,3 @@\n line 1\n-changed 2\n+line 2\n line 3\n\
+             @@ -15,3 +15,3 @@\n line 15\n-changed 16\n+line 16\n line 17\n"
+        );
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let (_, out) = diff(&changed(&[1, 4], 10), &lines(10).join("\n"), 1);
+        assert_eq!(out.matches("@@ -").count(), 1);
+        assert!(out.starts_with("@@ -1,6 +1,6 @@\n"));
+    }
+
+    #[test]
+    fn added_and_removed_lines() {
+        let (_, out) = diff("a\nb", "a\nb\nc", 0);
+        assert_eq!(out, "@@ -2,0 +3,1 @@\n+c\n");
+        let (_, out) = diff("a\nb\nc", "a\nc", 0);
+        assert_eq!(out, "@@ -2,1 +1,0 @@\n-b\n");
+    }
 }
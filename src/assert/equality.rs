@@ -0,0 +1,379 @@
+use protobuf::reflect::{
+    FieldDescriptor, ReflectFieldRef, ReflectValueRef, RuntimeFieldType, RuntimeType, Syntax,
+};
+use protobuf::text_format;
+use protobuf::{MessageDyn, UnknownValueRef};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fmt::{self, Formatter};
+
+/// Values longer than this are trimmed in diagnostics.
+const MAX_VALUE_LEN: usize = 80;
+
+/// How unknown fields take part in comparing messages.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownFields {
+    /// Unknown fields must match, in any order of field numbers.
+    #[default]
+    Compare,
+    Ignore,
+}
+
+/// A field that differs between two messages, e.g.
+/// `repeated_nested_message[2].corecursive.optional_int32: expected 5, got 0`.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Difference {
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.path,
+            trim(&self.expected),
+            trim(&self.actual)
+        )
+    }
+}
+
+fn trim(value: &str) -> String {
+    let value = value.replace('\n', " ");
+    let value = value.trim();
+    if value.chars().count() <= MAX_VALUE_LEN {
+        return value.to_string();
+    }
+    let trimmed: String = value.chars().take(MAX_VALUE_LEN).collect();
+    format!("{}...", trimmed)
+}
+
+/// Every field that differs between two messages, by path. Maps are
+/// compared regardless of entry order, NaN equals NaN while `0.0` and `-0.0`
+/// differ, and unknown fields are compared as told.
+pub fn differences(
+    actual: &dyn MessageDyn,
+    expected: &dyn MessageDyn,
+    unknown: UnknownFields,
+) -> Vec<Difference> {
+    let mut walker = Walker {
+        unknown,
+        differences: Vec::new(),
+    };
+    walker.messages("", actual, expected);
+    walker.differences
+}
+
+struct Walker {
+    unknown: UnknownFields,
+    differences: Vec<Difference>,
+}
+
+impl Walker {
+    fn differ(&mut self, path: String, actual: Option<String>, expected: Option<String>) {
+        let unset = || String::from("<unset>");
+        self.differences.push(Difference {
+            path,
+            expected: expected.unwrap_or_else(unset),
+            actual: actual.unwrap_or_else(unset),
+        });
+    }
+
+    fn messages(&mut self, path: &str, actual: &dyn MessageDyn, expected: &dyn MessageDyn) {
+        let descriptor = actual.descriptor_dyn();
+        if descriptor != expected.descriptor_dyn() {
+            self.differ(
+                path.to_string(),
+                Some(descriptor.full_name().to_string()),
+                Some(expected.descriptor_dyn().full_name().to_string()),
+            );
+            return;
+        }
+
+        let join = |name: &str| {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", path, name)
+            }
+        };
+
+        for field in descriptor.fields() {
+            let path = join(field.name());
+            match (field.get_reflect(actual), field.get_reflect(expected)) {
+                (ReflectFieldRef::Optional(a), ReflectFieldRef::Optional(e)) => {
+                    if !has_presence(&field) {
+                        // an unset field reads as its default, like a set one
+                        let a = field.get_singular_field_or_default(actual);
+                        let e = field.get_singular_field_or_default(expected);
+                        self.values(path, &a, &e);
+                        continue;
+                    }
+                    match (a.value(), e.value()) {
+                        (None, None) => {}
+                        (Some(a), Some(e)) => self.values(path, &a, &e),
+                        (a, e) => self.differ(path, a.map(|a| display(&a)), e.map(|e| display(&e))),
+                    }
+                }
+                (ReflectFieldRef::Repeated(a), ReflectFieldRef::Repeated(e)) => {
+                    for i in 0..a.len().max(e.len()) {
+                        let path = format!("{}[{}]", path, i);
+                        match (i < a.len(), i < e.len()) {
+                            (true, true) => self.values(path, &a.get(i), &e.get(i)),
+                            (true, false) => self.differ(path, Some(display(&a.get(i))), None),
+                            _ => self.differ(path, None, Some(display(&e.get(i)))),
+                        }
+                    }
+                }
+                (ReflectFieldRef::Map(a), ReflectFieldRef::Map(e)) => {
+                    // keys in order, so that differences come out the same every run
+                    let mut keys: Vec<ReflectValueRef> = a.into_iter().map(|(k, _)| k).collect();
+                    keys.extend(
+                        e.into_iter()
+                            .map(|(k, _)| k)
+                            .filter(|k| a.get(k.clone()).is_none()),
+                    );
+                    keys.sort_by(compare_keys);
+                    for k in keys {
+                        let path = format!("{}[{}]", path, display(&k));
+                        match (a.get(k.clone()), e.get(k)) {
+                            (Some(av), Some(ev)) => self.values(path, &av, &ev),
+                            (av, ev) => {
+                                self.differ(path, av.map(|v| display(&v)), ev.map(|v| display(&v)))
+                            }
+                        }
+                    }
+                }
+                _ => unreachable!("field {} has a different kind on each side", path),
+            }
+        }
+
+        if self.unknown == UnknownFields::Compare {
+            let (a, e) = (unknown_fields(actual), unknown_fields(expected));
+            if a != e {
+                self.differ(
+                    join("<unknown fields>"),
+                    Some(format!("{:?}", a)),
+                    Some(format!("{:?}", e)),
+                );
+            }
+        }
+    }
+
+    fn values(&mut self, path: String, actual: &ReflectValueRef, expected: &ReflectValueRef) {
+        let same = match (actual, expected) {
+            (ReflectValueRef::F32(a), ReflectValueRef::F32(e)) => {
+                floats_equal(*a as f64, *e as f64)
+            }
+            (ReflectValueRef::F64(a), ReflectValueRef::F64(e)) => floats_equal(*a, *e),
+            (ReflectValueRef::Enum(_, a), ReflectValueRef::Enum(_, e)) => a == e,
+            (ReflectValueRef::Message(a), ReflectValueRef::Message(e)) => {
+                return self.messages(&path, &**a, &**e);
+            }
+            _ => actual == expected,
+        };
+        if !same {
+            self.differ(path, Some(display(actual)), Some(display(expected)));
+        }
+    }
+}
+
+fn display(value: &ReflectValueRef) -> String {
+    match value {
+        ReflectValueRef::String(s) => format!("{:?}", s),
+        ReflectValueRef::Message(m) => format!("{{ {} }}", text_format::print_to_string(&**m)),
+        ReflectValueRef::Enum(descriptor, v) => match descriptor.value_by_number(*v) {
+            Some(value) => value.name().to_string(),
+            None => v.to_string(),
+        },
+        _ => value.to_string(),
+    }
+}
+
+fn compare_keys(left: &ReflectValueRef, right: &ReflectValueRef) -> Ordering {
+    match (left, right) {
+        (ReflectValueRef::I32(l), ReflectValueRef::I32(r)) => l.cmp(r),
+        (ReflectValueRef::I64(l), ReflectValueRef::I64(r)) => l.cmp(r),
+        (ReflectValueRef::U32(l), ReflectValueRef::U32(r)) => l.cmp(r),
+        (ReflectValueRef::U64(l), ReflectValueRef::U64(r)) => l.cmp(r),
+        (ReflectValueRef::Bool(l), ReflectValueRef::Bool(r)) => l.cmp(r),
+        (ReflectValueRef::String(l), ReflectValueRef::String(r)) => l.cmp(r),
+        _ => display(left).cmp(&display(right)),
+    }
+}
+
+/// Whether `field` tells being unset apart from holding its default, as
+/// message fields, oneof members and proto2 or proto3 `optional` fields do.
+fn has_presence(field: &FieldDescriptor) -> bool {
+    matches!(
+        field.runtime_field_type(),
+        RuntimeFieldType::Singular(RuntimeType::Message(_))
+    ) || field.containing_oneof_including_synthetic().is_some()
+        || field.containing_message().file_descriptor().syntax() == Syntax::Proto2
+}
+
+fn floats_equal(left: f64, right: f64) -> bool {
+    if left.is_nan() || right.is_nan() {
+        return left.is_nan() && right.is_nan();
+    }
+    left == right && left.is_sign_negative() == right.is_sign_negative()
+}
+
+/// Unknown fields ordered by field number, keeping the order of the values
+/// of a single field.
+fn unknown_fields(message: &dyn MessageDyn) -> Vec<(u32, UnknownValueRef<'_>)> {
+    let mut fields: Vec<(u32, UnknownValueRef)> = message.unknown_fields_dyn().iter().collect();
+    fields.sort_by_key(|(number, _)| *number);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::test_messages_proto2::TestAllTypesProto2;
+    use crate::assert::test_messages_proto3::TestAllTypesProto3;
+    use protobuf::well_known_types::wrappers::FloatValue;
+    use protobuf::{Message, MessageField};
+
+    fn paths(actual: &dyn MessageDyn, expected: &dyn MessageDyn) -> Vec<String> {
+        differences(actual, expected, UnknownFields::Compare)
+            .into_iter()
+            .map(|d| d.path)
+            .collect()
+    }
+
+    #[test]
+    fn map_order_does_not_matter() {
+        let mut actual = TestAllTypesProto3::new();
+        let mut expected = TestAllTypesProto3::new();
+        for i in 0..16 {
+            actual.map_int32_int32.insert(i, i * 10);
+            expected.map_int32_int32.insert(15 - i, (15 - i) * 10);
+        }
+        assert!(paths(&actual, &expected).is_empty());
+
+        expected.map_int32_int32.insert(3, 31);
+        assert_eq!(
+            differences(&actual, &expected, UnknownFields::Compare)[0].to_string(),
+            "map_int32_int32[3]: expected 31, got 30"
+        );
+    }
+
+    #[test]
+    fn map_differences_are_ordered_by_key() {
+        let mut actual = TestAllTypesProto3::new();
+        let mut expected = TestAllTypesProto3::new();
+        for i in [-3, 12, 1, 100, -40, 7] {
+            actual.map_int32_int32.insert(i, i);
+            expected.map_int32_int32.insert(i, i + 1);
+        }
+        actual.map_int32_int32.insert(50, 50);
+        expected.map_int32_int32.insert(2, 2);
+        expected
+            .map_string_string
+            .insert("b".to_string(), String::new());
+        expected
+            .map_string_string
+            .insert("a".to_string(), String::new());
+        assert_eq!(
+            paths(&actual, &expected),
+            [
+                "map_int32_int32[-40]",
+                "map_int32_int32[-3]",
+                "map_int32_int32[1]",
+                "map_int32_int32[2]",
+                "map_int32_int32[7]",
+                "map_int32_int32[12]",
+                "map_int32_int32[50]",
+                "map_int32_int32[100]",
+                "map_string_string[\"a\"]",
+                "map_string_string[\"b\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn nan_equals_nan() {
+        let mut actual = TestAllTypesProto3::new();
+        actual.optional_double = f64::NAN;
+        actual.repeated_float = vec![f32::NAN];
+        let expected = actual.clone();
+        assert!(paths(&actual, &expected).is_empty());
+
+        let mut expected = actual.clone();
+        expected.optional_double = 1.0;
+        assert_eq!(paths(&actual, &expected), ["optional_double"]);
+    }
+
+    #[test]
+    fn zeros_of_another_sign_differ() {
+        let mut actual = TestAllTypesProto2::new();
+        actual.optional_double = Some(-0.0);
+        actual.optional_float = Some(-0.0);
+        let mut expected = TestAllTypesProto2::new();
+        expected.optional_double = Some(0.0);
+        expected.optional_float = Some(0.0);
+        assert_eq!(
+            paths(&actual, &expected),
+            ["optional_float", "optional_double"]
+        );
+        assert!(paths(&actual, &actual.clone()).is_empty());
+
+        let mut repeated = TestAllTypesProto3::new();
+        repeated.repeated_double = vec![0.0];
+        let mut negative = repeated.clone();
+        negative.repeated_double = vec![-0.0];
+        assert_eq!(paths(&negative, &repeated), ["repeated_double[0]"]);
+    }
+
+    #[test]
+    fn fields_without_presence_compare_their_defaults() {
+        let mut wrapped = TestAllTypesProto3::new();
+        wrapped.optional_float_wrapper = MessageField::some(FloatValue::new());
+        assert_eq!(
+            paths(&wrapped, &TestAllTypesProto3::new()),
+            ["optional_float_wrapper"]
+        );
+
+        // reflection reads -0.0 as unset in these, just like 0.0
+        let mut actual = TestAllTypesProto3::new();
+        actual.optional_float = -0.0;
+        assert!(paths(&actual, &TestAllTypesProto3::new()).is_empty());
+    }
+
+    #[test]
+    fn repeated_order_matters() {
+        let mut actual = TestAllTypesProto3::new();
+        actual.repeated_int32 = vec![1, 2, 3];
+        let mut expected = TestAllTypesProto3::new();
+        expected.repeated_int32 = vec![3, 2, 1];
+        assert_eq!(
+            paths(&actual, &expected),
+            ["repeated_int32[0]", "repeated_int32[2]"]
+        );
+
+        expected.repeated_int32 = vec![1, 2];
+        let differences = differences(&actual, &expected, UnknownFields::Compare);
+        assert_eq!(
+            differences[0].to_string(),
+            "repeated_int32[2]: expected <unset>, got 3"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_compared_as_told() {
+        let mut actual = TestAllTypesProto3::new();
+        actual.mut_unknown_fields().add_varint(9000, 1);
+        actual.mut_unknown_fields().add_varint(9001, 2);
+        let mut expected = TestAllTypesProto3::new();
+        expected.mut_unknown_fields().add_varint(9001, 2);
+        expected.mut_unknown_fields().add_varint(9000, 1);
+        assert!(paths(&actual, &expected).is_empty());
+
+        let expected = TestAllTypesProto3::new();
+        assert_eq!(paths(&actual, &expected), ["<unknown fields>"]);
+        assert!(differences(&actual, &expected, UnknownFields::Ignore).is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use super::{test_messages_proto2::TestAllTypesProto2, test_messages_proto3::TestAllTypesProto3};
 use anyhow::{anyhow, Ok};
-use protobuf::Message;
+use protobuf::{Message, MessageDyn};
 use protobuf_json_mapping::parse_from_str;
 
 pub enum TestMessage {
@@ -15,6 +15,36 @@ impl TestMessage {
             Self::Proto3(proto3) => protobuf::text_format::print_to_string_pretty(proto3),
         }
     }
+    pub fn as_dyn(&self) -> &dyn MessageDyn {
+        match self {
+            Self::Proto2(proto2) => proto2,
+            Self::Proto3(proto3) => proto3,
+        }
+    }
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Proto2(proto2) => Ok(proto2.write_to_bytes()?),
+            Self::Proto3(proto3) => Ok(proto3.write_to_bytes()?),
+        }
+    }
+    pub fn syntax_of(message_type: &str) -> anyhow::Result<&'static str> {
+        match message_type {
+            "protobuf_test_messages.proto3.TestAllTypesProto3" => Ok("proto3"),
+            "protobuf_test_messages.proto2.TestAllTypesProto2" => Ok("proto2"),
+            _ => Err(anyhow!("unknown message type {}", message_type)),
+        }
+    }
+    pub fn from_text(message_type: &str, text: &str) -> anyhow::Result<Self> {
+        match message_type {
+            "protobuf_test_messages.proto3.TestAllTypesProto3" => Ok(TestMessage::Proto3(
+                protobuf::text_format::parse_from_str::<TestAllTypesProto3>(text)?,
+            )),
+            "protobuf_test_messages.proto2.TestAllTypesProto2" => Ok(TestMessage::Proto2(
+                protobuf::text_format::parse_from_str::<TestAllTypesProto2>(text)?,
+            )),
+            _ => Err(anyhow!("unknown message type {}", message_type)),
+        }
+    }
     pub fn from_protobuf_payload(message_type: &str, payload: &[u8]) -> anyhow::Result<Self> {
         match message_type {
             "protobuf_test_messages.proto3.TestAllTypesProto3" => Ok(TestMessage::Proto3(
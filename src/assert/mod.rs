@@ -3,4 +3,5 @@ include!(concat!(env!("OUT_DIR"), "/conformance/mod.rs"));
 pub mod message;
 pub mod case;
 pub mod differ;
+pub mod equality;
 pub mod validator;
\ No newline at end of file
@@ -1,181 +1,290 @@
-use crate::test_case::TestCase;
-use anyhow::anyhow;
-pub type Validator = fn(serde_json::Value) -> anyhow::Result<()>;
-
-fn assert_map_contains_key(
-    map: &serde_json::Map<String, serde_json::Value>,
-    key: &str,
-) -> anyhow::Result<bool> {
-    if !map.contains_key(key) {
-        return Err(anyhow!("json value doesn't contain the key `{}`", key));
-    }
-    Ok(true)
-}
+use crate::test_case::{AssertBy, TestCase};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
 
-fn assert_map_not_contains_key(
-    map: &serde_json::Map<String, serde_json::Value>,
-    key: &str,
-) -> anyhow::Result<bool> {
-    if map.contains_key(key) {
-        return Err(anyhow!("json value should contain the key `{}`", key));
-    }
-    Ok(true)
-}
+/// Validators of the upstream cases, by case name.
+const BUILTIN: &str = include_str!("validators.json");
 
-macro_rules! assert_object_and_has_keys {
-    ( $( $f:literal ),* ) => {
-        |v| {
-            let obj = v.as_object().ok_or(anyhow!("value is not an object."))?;
-            $(
-                assert_map_contains_key(obj, $f)?;
-            )*
-            Ok(())
-        }
-    };
+/// Json type a value must have.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    Integer,
+    String,
+    Array,
+    Object,
 }
 
-macro_rules! assert_object_and_key_value_and_type {
-    ( $f:literal, $cast:ident, $eq:expr ) => {
-        |v: serde_json::Value| {
-            let obj = v.as_object().ok_or(anyhow!("value is not an object."))?;
-            let v = obj
-                .get($f)
-                .ok_or(anyhow!("json value should contain the key `{}`", $f))?
-                .$cast()
-                .ok_or(anyhow!("{} is not a {}.", $f, stringify!($cast)))?;
-
-            if v != $eq {
-                return Err(anyhow!("{} was not equal to {}", $f, $eq));
-            }
-            Ok(())
+impl JsonType {
+    fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            Self::Null => value.is_null(),
+            Self::Boolean => value.is_boolean(),
+            Self::Number => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::String => value.is_string(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
         }
-    };
+    }
 }
 
-macro_rules! assert_object_and_is_null {
-    ( $f:literal) => {
-        |v: serde_json::Value| {
-            let obj = v.as_object().ok_or(anyhow!("value is not an object."))?;
-            obj.get($f)
-                .ok_or(anyhow!("json value should contain the key `{}`", $f))?
-                .as_null()
-                .ok_or(anyhow!("{} is not null", $f))?;
-            Ok(())
-        }
-    };
+/// Declarative assertion on the value at a json pointer of the response.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    pub pointer: String,
+    /// Whether the value must be present or absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub present: Option<bool>,
+    /// Type the value must have.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<JsonType>,
+    /// Value the value must be equal to, type included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<serde_json::Value>,
+    /// Regular expression a string value must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
 }
 
-impl From<&TestCase> for Validator {
-    fn from(case: &TestCase) -> Self {
-        match case.name.as_str() {
-            "Required.Proto3.JsonInput.FieldNameInLowerCamelCase.Validator" => {
-                assert_object_and_has_keys!("fieldname1", "fieldName2", "FieldName3", "fieldName4")
-            }
+impl Check {
+    fn target(&self) -> &str {
+        if self.pointer.is_empty() {
+            "the json value"
+        } else {
+            &self.pointer
+        }
+    }
 
-            "Required.Proto3.JsonInput.FieldNameWithNumbers.Validator" => {
-                assert_object_and_has_keys!("field0name5", "field0Name6")
+    pub fn check(&self, value: &serde_json::Value) -> anyhow::Result<()> {
+        let found = value.pointer(&self.pointer);
+        match (self.present, found) {
+            (Some(true), None) => {
+                return Err(anyhow!("json value doesn't contain `{}`", self.pointer))
             }
-
-            "Required.Proto3.JsonInput.FieldNameWithMixedCases.Validator" => {
-                assert_object_and_has_keys!(
-                    "fieldName7",
-                    "FieldName8",
-                    "fieldName9",
-                    "FieldName10",
-                    "FIELDNAME11",
-                    "FIELDName12"
-                )
+            (Some(false), Some(_)) => {
+                return Err(anyhow!("json value shouldn't contain `{}`", self.pointer))
             }
+            (Some(false), None) => return Ok(()),
+            _ => {}
+        }
 
-            "Recommended.Proto3.JsonInput.FieldNameWithDoubleUnderscores.Validator" => {
-                assert_object_and_has_keys!(
-                    "FieldName13",
-                    "FieldName14",
-                    "fieldName15",
-                    "fieldName16",
-                    "fieldName17",
-                    "FieldName18"
-                )
+        let needs_value = self.kind.is_some() || self.equals.is_some() || self.regex.is_some();
+        let found = match found {
+            Some(found) => found,
+            None if needs_value => {
+                return Err(anyhow!("json value doesn't contain `{}`", self.pointer))
             }
+            None => return Ok(()),
+        };
 
-            "Required.Proto3.JsonInput.SkipsDefaultPrimitive.Validator" => |v| {
-                let obj = v.as_object().ok_or(anyhow!("value is not an object."))?;
-                assert_map_not_contains_key(obj, "FieldName13")?;
-                Ok(())
-            },
-            "Recommended.Proto3.JsonInput.Int64FieldBeString.Validator" => {
-                assert_object_and_key_value_and_type!("optionalInt64", as_str, "1")
-            }
-            "Recommended.Proto3.JsonInput.Uint64FieldBeString.Validator" => {
-                assert_object_and_key_value_and_type!("optionalUint64", as_str, "1")
-            }
-            "Required.Proto3.JsonInput.EnumFieldUnknownValue.Validator" => {
-                assert_object_and_key_value_and_type!("optionalNestedEnum", as_i64, 123)
-            }
-            "Recommended.Proto3.JsonInput.DurationHasZeroFractionalDigit.Validator" => {
-                assert_object_and_key_value_and_type!("optionalDuration", as_str, "1s")
-            }
-            "Recommended.Proto3.JsonInput.DurationHas3FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!("optionalDuration", as_str, "1.010s")
+        if let Some(kind) = &self.kind {
+            if !kind.matches(found) {
+                return Err(anyhow!(
+                    "{} is not of type {}",
+                    self.target(),
+                    serde_json::to_string(kind)?.trim_matches('"')
+                ));
             }
-            "Recommended.Proto3.JsonInput.DurationHas6FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!("optionalDuration", as_str, "1.000010s")
-            }
-            "Recommended.Proto3.JsonInput.DurationHas9FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!("optionalDuration", as_str, "1.000000010s")
-            }
-            "Recommended.Proto3.JsonInput.TimestampZeroNormalized.Validator" => {
-                assert_object_and_key_value_and_type!(
-                    "optionalTimestamp",
-                    as_str,
-                    "1970-01-01T00:00:00Z"
-                )
-            }
-            "Recommended.Proto3.JsonInput.TimestampHasZeroFractionalDigit.Validator" => {
-                assert_object_and_key_value_and_type!(
-                    "optionalTimestamp",
-                    as_str,
-                    "1970-01-01T00:00:00Z"
-                )
-            }
-            "Recommended.Proto3.JsonInput.TimestampHas3FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!(
-                    "optionalTimestamp",
-                    as_str,
-                    "1970-01-01T00:00:00.010Z"
-                )
-            }
-            "Recommended.Proto3.JsonInput.TimestampHas6FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!(
-                    "optionalTimestamp",
-                    as_str,
-                    "1970-01-01T00:00:00.000010Z"
-                )
-            }
-            "Recommended.Proto3.JsonInput.TimestampHas9FractionalDigits.Validator" => {
-                assert_object_and_key_value_and_type!(
-                    "optionalTimestamp",
-                    as_str,
-                    "1970-01-01T00:00:00.000000010Z"
-                )
+        }
+        if let Some(expected) = &self.equals {
+            if found != expected {
+                return Err(anyhow!(
+                    "{} was {} instead of {}",
+                    self.target(),
+                    found,
+                    expected
+                ));
             }
-            "Recommended.Proto3.JsonInput.NullValueInOtherOneofOldFormat.Validator" => {
-                assert_object_and_key_value_and_type!("oneofNullValue", as_str, "NULL_VALUE")
+        }
+        if let Some(pattern) = &self.regex {
+            let re =
+                regex::Regex::new(pattern).with_context(|| format!("invalid regex {}", pattern))?;
+            let s = found
+                .as_str()
+                .ok_or(anyhow!("{} is not a string", self.target()))?;
+            if !re.is_match(s) {
+                return Err(anyhow!("{} doesn't match {}", self.target(), pattern));
             }
-            "Recommended.Proto3.JsonInput.NullValueInOtherOneofNewFormat.Validator" => {
-                assert_object_and_is_null!("oneofNullValue")
+        }
+        Ok(())
+    }
+}
+
+pub fn check_all(checks: &[Check], value: &serde_json::Value) -> anyhow::Result<()> {
+    for check in checks {
+        check.check(value)?;
+    }
+    Ok(())
+}
+
+/// Json validators by case name, a json object of
+/// `{ "<case name>": [<check>, ...] }`.
+#[derive(Default)]
+pub struct Validators {
+    by_case: HashMap<String, Vec<Check>>,
+}
+
+impl Validators {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("failed to parse the built-in validators")
+    }
+
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let by_case = serde_json::from_str(json).context("failed to parse validators")?;
+        Ok(Self { by_case })
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read validators {}", path))?;
+        Self::parse(&json).with_context(|| format!("failed to load validators {}", path))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Vec<Check>> {
+        self.by_case.get(name)
+    }
+
+    /// Gives the validator cases their checks. Overrides win over the checks a
+    /// case already has, which in turn win over the built-in ones.
+    pub fn apply(&self, overrides: &Validators, cases: &mut [TestCase]) {
+        for case in cases.iter_mut() {
+            let name = case.name.as_str();
+            if let AssertBy::Validator(checks) = &mut case.assert_by {
+                if let Some(overridden) = overrides.get(name) {
+                    *checks = Some(overridden.clone());
+                } else if checks.is_none() {
+                    *checks = self.get(name).cloned();
+                }
             }
-            "Recommended.Proto3.JsonInput.NullValueInNormalMessage.Validator" => |v| {
-                let obj = v.as_object().ok_or(anyhow!("value is not an object."))?;
-                if obj.keys().len() > 0 {
-                    return Err(anyhow!("value should be an empty object."));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(json: serde_json::Value) -> Check {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn error(check: serde_json::Value, value: serde_json::Value) -> String {
+        self::check(check).check(&value).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn pointer() {
+        let value = json!({ "optionalInt32": 1, "nested": { "a": [true] } });
+        assert!(check(json!({ "pointer": "/nested/a/0" }))
+            .check(&value)
+            .is_ok());
+        // a pointer with nothing to check only needs to resolve when present is asked
+        assert!(check(json!({ "pointer": "/missing" }))
+            .check(&value)
+            .is_ok());
+        assert_eq!(
+            error(json!({ "pointer": "/missing", "type": "number" }), value),
+            "json value doesn't contain `/missing`"
+        );
+    }
+
+    #[test]
+    fn present() {
+        let value = json!({ "optionalInt32": 1 });
+        let present = json!({ "pointer": "/optionalInt32", "present": true });
+        let absent = json!({ "pointer": "/optionalInt32", "present": false });
+        assert!(check(present.clone()).check(&value).is_ok());
+        assert_eq!(
+            error(absent.clone(), value),
+            "json value shouldn't contain `/optionalInt32`"
+        );
+        assert!(check(absent).check(&json!({})).is_ok());
+        assert_eq!(
+            error(present, json!({})),
+            "json value doesn't contain `/optionalInt32`"
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let value = json!({ "optionalInt64": "1", "optionalDouble": 1.5 });
+        assert!(
+            check(json!({ "pointer": "/optionalInt64", "type": "string" }))
+                .check(&value)
+                .is_ok()
+        );
+        assert_eq!(
+            error(
+                json!({ "pointer": "/optionalDouble", "type": "integer" }),
+                value.clone()
+            ),
+            "/optionalDouble is not of type integer"
+        );
+        assert_eq!(
+            error(json!({ "pointer": "", "type": "array" }), value),
+            "the json value is not of type array"
+        );
+    }
+
+    #[test]
+    fn equals() {
+        let value = json!({ "optionalInt64": "1" });
+        assert!(check(json!({ "pointer": "/optionalInt64", "equals": "1" }))
+            .check(&value)
+            .is_ok());
+        // the type takes part in the comparison
+        assert_eq!(
+            error(json!({ "pointer": "/optionalInt64", "equals": 1 }), value),
+            "/optionalInt64 was \"1\" instead of 1"
+        );
+    }
+
+    #[test]
+    fn regex() {
+        let value = json!({ "optionalDuration": "1.010s", "optionalInt32": 1 });
+        let duration = |re: &str| check(json!({ "pointer": "/optionalDuration", "regex": re }));
+        assert!(duration("^1\\.010s$").check(&value).is_ok());
+        assert_eq!(
+            duration("^1\\.01s$").check(&value).unwrap_err().to_string(),
+            "/optionalDuration doesn't match ^1\\.01s$"
+        );
+        assert_eq!(
+            error(
+                json!({ "pointer": "/optionalInt32", "regex": "1" }),
+                value.clone()
+            ),
+            "/optionalInt32 is not a string"
+        );
+        assert!(duration("(").check(&value).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let checks = r#"{ "Case": [{ "pointer": "", "typ": "object" }] }"#;
+        assert!(Validators::parse(checks).is_err());
+    }
+
+    #[test]
+    fn loads_the_builtin_validators() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/assert/validators.json");
+        let validators = Validators::load(path).unwrap();
+        assert!(!validators.by_case.is_empty());
+        assert_eq!(validators.by_case, Validators::builtin().by_case);
+        for (name, checks) in &validators.by_case {
+            assert!(name.ends_with(".Validator"), "{}", name);
+            for check in checks {
+                if let Some(pattern) = &check.regex {
+                    assert!(regex::Regex::new(pattern).is_ok(), "{}: {}", name, pattern);
                 }
-                Ok(())
-            },
-            "Required.Proto2.JsonInput.StoresDefaultPrimitive.Validator" => {
-                assert_object_and_key_value_and_type!("FieldName13", as_i64, 0)
             }
-            _ => |_| Err(anyhow!("unimplemented validator")),
         }
     }
 }
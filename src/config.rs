@@ -0,0 +1,170 @@
+use crate::report::fanout::Spec;
+use crate::stats;
+use crate::test_case::FORMATS;
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the configuration file picked up from the working directory.
+pub const DEFAULT_PATH: &str = "conform.toml";
+
+/// Project configuration, read from `conform.toml`. Every key mirrors the
+/// command line flag of the same name, flags given on the command line take
+/// precedence over the values in the file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub program: Option<String>,
+    /// Several programs by name, to run them all against the suite.
+    #[serde(default)]
+    pub programs: BTreeMap<String, String>,
+    pub parallel: Option<bool>,
+    pub matrix: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub program_cwd: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub runner_stderr: Option<String>,
+    #[serde(default)]
+    pub filter: Vec<String>,
+    #[serde(default)]
+    pub skip_format: Vec<String>,
+    pub failure_list: Option<String>,
+    /// Seconds to wait for the response of a single case.
+    pub timeout: Option<f64>,
+    pub record: Option<String>,
+    pub suite: Option<String>,
+    pub cases_dir: Option<String>,
+    pub validators: Option<String>,
+    pub output: Option<String>,
+    #[serde(default)]
+    pub reporters: Vec<Spec>,
+    pub json_stats: Option<String>,
+    pub slowest: Option<usize>,
+    pub exit_early: Option<bool>,
+    pub enforce_recommended: Option<bool>,
+    pub min_required_score: Option<f32>,
+    pub min_recommended_score: Option<f32>,
+}
+
+/// Whether `name` can name a program, as in `--program name=path`.
+pub fn is_program_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl Config {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("failed to read config {}", path))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config {}", path))?;
+        if let Some(name) = config.programs.keys().find(|name| !is_program_name(name)) {
+            return Err(anyhow!(
+                "invalid config {}: program name {:?} must start with a letter or `_` and only contain letters, digits, `_` and `-`",
+                path,
+                name
+            ));
+        }
+        for score in [config.min_required_score, config.min_recommended_score]
+            .into_iter()
+            .flatten()
+        {
+            stats::check_score(score).with_context(|| format!("invalid config {}", path))?;
+        }
+        if let Some(format) = config
+            .skip_format
+            .iter()
+            .find(|format| !FORMATS.contains(&format.as_str()))
+        {
+            return Err(anyhow!(
+                "invalid config {}: skip_format {:?} is not one of {}",
+                path,
+                format,
+                FORMATS.join(", ")
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Loads `conform.toml` from the working directory if there is one.
+    pub fn discover() -> anyhow::Result<Option<Self>> {
+        if !Path::new(DEFAULT_PATH).exists() {
+            return Ok(None);
+        }
+        Self::load(DEFAULT_PATH).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_kind_of_key() {
+        let config: Config = toml::from_str(indoc::indoc! {r#"
+            program = "node"
+            args = ["runner.js"]
+            exit_early = true
+            timeout = 2.5
+            reporters = ["tap", "junit=report.xml"]
+
+            [programs]
+            other = "./other"
+
+            [env]
+            NO_COLOR = "1"
+        "#})
+        .unwrap();
+        assert_eq!(config.program.as_deref(), Some("node"));
+        assert_eq!(config.args, ["runner.js"]);
+        assert_eq!(config.exit_early, Some(true));
+        assert_eq!(config.parallel, None);
+        assert_eq!(config.timeout, Some(2.5));
+        assert_eq!(config.reporters.len(), 2);
+        assert_eq!(config.programs["other"], "./other");
+        assert_eq!(config.env["NO_COLOR"], "1");
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let err = toml::from_str::<Config>("programm = \"node\"")
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("unknown field `programm`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn program_names() {
+        for name in ["ts", "protoc-gen-ts", "_v2", "Go_1"] {
+            assert!(is_program_name(name), "{}", name);
+        }
+        for name in ["", "1st", "-ts", "./runner", "/opt/k", "a b", "ts.js"] {
+            assert!(!is_program_name(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let path = std::env::temp_dir().join(format!("conform-{}.toml", std::process::id()));
+        fs::write(&path, "skip_format = [\"json\", \"yaml\"]").unwrap();
+        let err = Config::load(path.to_str().unwrap()).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("skip_format \"yaml\""), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_reporters() {
+        assert!(toml::from_str::<Config>("reporters = [\"html\"]").is_err());
+    }
+}
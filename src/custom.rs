@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Context};
+use protobuf::{text_format, Message};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::assert::conformance::{ConformanceRequest, WireFormat};
+use crate::assert::message::TestMessage;
+use crate::assert::validator::Check;
+use crate::test_case::{AssertBy, Level, TestCase};
+
+/// A case of our own, one per `.toml` file:
+///
+/// ```toml
+/// name = "Required.Proto3.ProtobufInput.Custom"
+/// level = "Required"
+/// request = '''
+/// protobuf_payload: "\010\001"
+/// message_type: "protobuf_test_messages.proto3.TestAllTypesProto3"
+/// requested_output_format: PROTOBUF
+/// '''
+///
+/// [expect]
+/// equivalent = "optional_int32: 1"
+/// ```
+///
+/// Instead of `equivalent`, `expect` takes `parse_error = true`,
+/// `serialize_error = true` or a list of json `checks`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CaseFile {
+    name: Option<String>,
+    level: Option<String>,
+    request: String,
+    expect: Expect,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expect {
+    equivalent: Option<String>,
+    #[serde(default)]
+    require_same_wire_format: bool,
+    #[serde(default)]
+    parse_error: bool,
+    #[serde(default)]
+    serialize_error: bool,
+    checks: Option<Vec<Check>>,
+}
+
+/// Loads every `.toml` case in `dir`, ordered by file name.
+pub fn load_dir(dir: &str) -> anyhow::Result<Vec<TestCase>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("failed to read cases from {}", dir))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("failed to read cases from {}", dir))?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "toml"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| load(path).with_context(|| format!("failed to load {}", path.display())))
+        .collect()
+}
+
+pub fn load(path: &Path) -> anyhow::Result<TestCase> {
+    let contents = fs::read_to_string(path)?;
+    let stem = path
+        .file_stem()
+        .ok_or(anyhow!("case file has no name"))?
+        .to_string_lossy();
+    parse(&contents, &stem)
+}
+
+/// Parses the contents of a case file, named after `stem` unless it has a
+/// name of its own.
+fn parse(contents: &str, stem: &str) -> anyhow::Result<TestCase> {
+    let file: CaseFile = toml::from_str(contents)?;
+
+    let request = text_format::parse_from_str::<ConformanceRequest>(&file.request)
+        .context("failed to parse the request")?;
+    let syntax = TestMessage::syntax_of(&request.message_type)?.to_string();
+
+    let expect = file.expect;
+    let assert_by = match (
+        expect.equivalent,
+        expect.parse_error,
+        expect.serialize_error,
+        expect.checks,
+    ) {
+        (Some(equivalent), false, false, None) => {
+            match request.requested_output_format.enum_value() {
+                Ok(WireFormat::PROTOBUF | WireFormat::JSON | WireFormat::TEXT_FORMAT) => {}
+                _ => {
+                    return Err(anyhow!(
+                    "equivalent needs a requested_output_format of PROTOBUF, JSON or TEXT_FORMAT"
+                ))
+                }
+            }
+            let message = TestMessage::from_text(&request.message_type, &equivalent)
+                .context("failed to parse the equivalent message")?;
+            AssertBy::Equivalence(message.to_bytes()?, expect.require_same_wire_format)
+        }
+        (None, true, false, None) => AssertBy::ParseError,
+        (None, false, true, None) => AssertBy::SerializationError,
+        (None, false, false, Some(checks)) => {
+            if request.requested_output_format.enum_value() != Ok(WireFormat::JSON) {
+                return Err(anyhow!("checks need a requested_output_format of JSON"));
+            }
+            AssertBy::Validator(Some(checks))
+        }
+        _ => {
+            return Err(anyhow!(
+                "expect exactly one of equivalent, parse_error, serialize_error or checks"
+            ))
+        }
+    };
+
+    let name = file.name.unwrap_or_else(|| stem.to_string());
+
+    Ok(TestCase {
+        name,
+        level: Level::try_from(file.level.as_deref().unwrap_or("Required"))?,
+        syntax,
+        payload: request.write_to_bytes()?,
+        assert_by,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::case;
+    use crate::assert::conformance::{conformance_response, ConformanceResponse};
+
+    const REQUEST: &str = r#"
+request = '''
+protobuf_payload: "\010\001"
+message_type: "protobuf_test_messages.proto3.TestAllTypesProto3"
+requested_output_format: TEXT_FORMAT
+'''
+"#;
+
+    fn case(rest: &str) -> anyhow::Result<TestCase> {
+        parse(&format!("{}{}", REQUEST, rest), "Custom")
+    }
+
+    fn error(result: anyhow::Result<TestCase>) -> String {
+        format!("{:#}", result.err().expect("the case should not load"))
+    }
+
+    #[test]
+    fn name_and_level_default() {
+        let case = case("[expect]\nparse_error = true\n").unwrap();
+        assert_eq!(case.name, "Custom");
+        assert!(case.level == Level::Required);
+        assert_eq!(case.syntax, "proto3");
+        assert!(case.assert_by == AssertBy::ParseError);
+    }
+
+    #[test]
+    fn text_output_is_asserted_by_equivalence() {
+        let case = case("[expect]\nequivalent = \"optional_int32: 1\"\n").unwrap();
+        let answer = |text: &str| {
+            let mut response = ConformanceResponse::new();
+            response.result = Some(conformance_response::Result::TextPayload(text.to_string()));
+            case::assert(&case, &response.write_to_bytes().unwrap())
+        };
+        assert!(answer("optional_int32: 1").passed());
+        assert!(answer("optional_int32: 2").failed());
+        assert!(answer("optional_int32: ").failed());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(
+            error(case("leve = \"Required\"\n[expect]\nparse_error = true\n"))
+                .contains("unknown field `leve`")
+        );
+        assert!(error(case("[expect]\nparse_eror = true\n")).contains("unknown field `parse_eror`"));
+    }
+
+    #[test]
+    fn rejects_bad_payloads() {
+        let request = "request = 'protobuf_payload: 1'\n[expect]\nparse_error = true\n";
+        assert!(error(parse(request, "Custom")).contains("failed to parse the request"));
+        assert!(
+            error(case("[expect]\nequivalent = \"optional_int32: x\"\n"))
+                .contains("failed to parse the equivalent message")
+        );
+        assert!(error(case("[expect]\nequivalent = \"no_such_field: 1\"\n"))
+            .contains("failed to parse the equivalent message"));
+    }
+
+    #[test]
+    fn equivalence_needs_an_output_format() {
+        for format in ["JSPB", "UNSPECIFIED"] {
+            let contents = REQUEST.replace("TEXT_FORMAT", format)
+                + "[expect]\nequivalent = \"optional_int32: 1\"\n";
+            assert!(error(parse(&contents, "Custom")).contains("requested_output_format"));
+        }
+        let contents = REQUEST.replace("TEXT_FORMAT", "JSPB") + "[expect]\nparse_error = true\n";
+        assert!(parse(&contents, "Custom").is_ok());
+        let checks = "[[expect.checks]]\npointer = \"/optionalInt32\"\npresent = true\n";
+        assert!(error(case(checks)).contains("requested_output_format of JSON"));
+        let contents = REQUEST.replace("TEXT_FORMAT", "JSON") + checks;
+        assert!(parse(&contents, "Custom").is_ok());
+    }
+
+    #[test]
+    fn expects_exactly_one_assertion() {
+        assert!(error(case("[expect]\n")).contains("expect exactly one"));
+        assert!(error(case(
+            "[expect]\nparse_error = true\nserialize_error = true\n"
+        ))
+        .contains("expect exactly one"));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::assert::conformance::{
+    conformance_response::Result, ConformanceRequest, ConformanceResponse,
+};
+use crate::assert::differ;
+use crate::assert::equality::{self, Difference, UnknownFields};
+use crate::assert::message::TestMessage;
+use anyhow::Context;
+use protobuf::Message;
+
+/// Two implementations answering the same request differently.
+pub struct Disagreement {
+    /// Fields that differ when both sides answered with a message, with the
+    /// reference as the expected side.
+    pub differences: Vec<Difference>,
+    pub diff: String,
+}
+
+/// A response in a form that two implementations can be compared on. Error
+/// messages are dropped and payloads are decoded into the test message, so
+/// encodings that yield the same message are considered equal.
+enum Answer {
+    Message(Box<TestMessage>),
+    Other(String),
+}
+
+impl Answer {
+    fn new(request: &ConformanceRequest, response: &ConformanceResponse) -> Self {
+        let message_type = request.message_type.as_str();
+        let decoded = match &response.result {
+            None => return Self::Other(String::from("no result")),
+            Some(Result::ParseError(_)) => return Self::Other(String::from("parse error")),
+            Some(Result::SerializeError(_)) => return Self::Other(String::from("serialize error")),
+            Some(Result::RuntimeError(_)) => return Self::Other(String::from("runtime error")),
+            Some(Result::TimeoutError(_)) => return Self::Other(String::from("timeout error")),
+            Some(Result::Skipped(_)) => return Self::Other(String::from("skipped")),
+            Some(Result::JspbPayload(jspb)) => {
+                return Self::Other(format!("jspb payload:\n{}", jspb))
+            }
+            Some(Result::ProtobufPayload(buf)) => {
+                TestMessage::from_protobuf_payload(message_type, buf)
+                    .map_err(|e| format!("invalid protobuf payload: {}", e))
+            }
+            Some(Result::JsonPayload(json)) => TestMessage::from_json_payload(message_type, json)
+                .map_err(|e| format!("invalid json payload: {}", e)),
+            Some(Result::TextPayload(text)) => TestMessage::from_text(message_type, text)
+                .map_err(|e| format!("invalid text payload: {}", e)),
+        };
+        match decoded {
+            Ok(message) => Self::Message(Box::new(message)),
+            Err(e) => Self::Other(e),
+        }
+    }
+
+    fn render(&self) -> String {
+        match self {
+            Self::Message(message) => format!("message:\n{}", message.to_text()),
+            Self::Other(other) => other.clone(),
+        }
+    }
+}
+
+/// Compares the responses of two implementations to the same request.
+/// Messages are compared field by field, so map entries may come in any order.
+pub fn compare(
+    request: &[u8],
+    left: &[u8],
+    right: &[u8],
+    context: usize,
+) -> anyhow::Result<Option<Disagreement>> {
+    let request =
+        ConformanceRequest::parse_from_bytes(request).context("failed to parse request")?;
+    let left = ConformanceResponse::parse_from_bytes(left)
+        .context("failed to parse response of the program")?;
+    let right = ConformanceResponse::parse_from_bytes(right)
+        .context("failed to parse response of the reference")?;
+
+    let left = Answer::new(&request, &left);
+    let right = Answer::new(&request, &right);
+    let (differences, diff) = match (&left, &right) {
+        (Answer::Message(l), Answer::Message(r)) => {
+            let differences = equality::differences(l.as_dyn(), r.as_dyn(), UnknownFields::Compare);
+            if differences.is_empty() {
+                return Ok(None);
+            }
+            let lines: Vec<String> = differences.iter().map(|d| d.to_string()).collect();
+            let diff = format!(
+                "fields that differ from the reference:\n{}",
+                lines.join("\n")
+            );
+            (differences, diff)
+        }
+        (Answer::Other(l), Answer::Other(r)) if l == r => return Ok(None),
+        _ => {
+            let (_, diff) = differ::diff(&left.render(), &right.render(), context);
+            (Vec::new(), diff)
+        }
+    };
+
+    Ok(Some(Disagreement { differences, diff }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::conformance::WireFormat;
+    use crate::assert::test_messages_proto3::TestAllTypesProto3;
+
+    const PROTO3: &str = "protobuf_test_messages.proto3.TestAllTypesProto3";
+
+    fn request() -> Vec<u8> {
+        let mut request = ConformanceRequest::new();
+        request.message_type = PROTO3.to_string();
+        request.set_protobuf_payload(Vec::new());
+        request.requested_output_format = WireFormat::PROTOBUF.into();
+        request.write_to_bytes().unwrap()
+    }
+
+    fn response(result: Result) -> Vec<u8> {
+        let mut response = ConformanceResponse::new();
+        response.result = Some(result);
+        response.write_to_bytes().unwrap()
+    }
+
+    /// Wire format of a message with a single `map_int32_int32` entry.
+    fn entry(key: i32, value: i32) -> Vec<u8> {
+        let mut message = TestAllTypesProto3::new();
+        message.map_int32_int32.insert(key, value);
+        message.write_to_bytes().unwrap()
+    }
+
+    /// Protobuf payload with the `map_int32_int32` entries in the given order.
+    fn map_payload(entries: &[(i32, i32)]) -> Vec<u8> {
+        let payload: Vec<u8> = entries.iter().flat_map(|(k, v)| entry(*k, *v)).collect();
+        response(Result::ProtobufPayload(payload))
+    }
+
+    #[test]
+    fn map_entries_in_another_order_agree() {
+        let entries: Vec<(i32, i32)> = (1..=8).map(|i| (i, i * 10)).collect();
+        let reversed: Vec<(i32, i32)> = entries.iter().rev().copied().collect();
+        for _ in 0..8 {
+            let disagreement = compare(
+                &request(),
+                &map_payload(&entries),
+                &map_payload(&reversed),
+                3,
+            )
+            .unwrap();
+            assert!(disagreement.is_none(), "{}", disagreement.unwrap().diff);
+        }
+    }
+
+    #[test]
+    fn encodings_of_the_same_message_agree() {
+        let text = "map_int32_int32 { key: 2 value: 20 } map_int32_int32 { key: 1 value: 10 }";
+        let text = response(Result::TextPayload(text.to_string()));
+        let protobuf = map_payload(&[(1, 10), (2, 20)]);
+        assert!(compare(&request(), &text, &protobuf, 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn differing_map_values_are_reported_by_path() {
+        let disagreement = compare(
+            &request(),
+            &map_payload(&[(1, 10), (2, 20)]),
+            &map_payload(&[(2, 21), (1, 10)]),
+            3,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(disagreement.differences.len(), 1);
+        assert_eq!(
+            disagreement.differences[0].to_string(),
+            "map_int32_int32[2]: expected 21, got 20"
+        );
+        assert!(disagreement
+            .diff
+            .contains("map_int32_int32[2]: expected 21, got 20"));
+    }
+
+    #[test]
+    fn error_messages_are_ignored() {
+        let left = response(Result::ParseError("unexpected end".to_string()));
+        let right = response(Result::ParseError("truncated message".to_string()));
+        assert!(compare(&request(), &left, &right, 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn error_against_a_message_disagrees() {
+        let left = response(Result::ParseError("unexpected end".to_string()));
+        let right = map_payload(&[(1, 10)]);
+        let disagreement = compare(&request(), &left, &right, 3).unwrap().unwrap();
+        assert!(disagreement.differences.is_empty());
+        assert!(disagreement.diff.contains("-parse error"));
+        assert!(disagreement.diff.contains("+message:"));
+    }
+}
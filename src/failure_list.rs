@@ -0,0 +1,88 @@
+use anyhow::Context;
+use std::collections::HashSet;
+use std::fs;
+
+/// Cases that are known to fail, one name per line. Anything after a `#` is
+/// a comment, same as the failure lists of the upstream runner.
+#[derive(Default)]
+pub struct FailureList {
+    names: HashSet<String>,
+}
+
+impl FailureList {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read failure list {}", path))?;
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let names = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+        Self { names }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_name_per_line() {
+        let list = FailureList::parse("Required.A\nRecommended.B\n");
+        assert_eq!(list.len(), 2);
+        assert!(list.contains("Required.A"));
+        assert!(list.contains("Recommended.B"));
+        assert!(!list.contains("Required"));
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let list = FailureList::parse(indoc::indoc! {"
+            # known failures
+            Required.A  # fails on windows
+
+              Required.B
+            #Required.C
+        "});
+        assert_eq!(list.len(), 2);
+        assert!(list.contains("Required.A"));
+        assert!(list.contains("Required.B"));
+        assert!(!list.contains("Required.C"));
+    }
+
+    #[test]
+    fn empty() {
+        assert!(FailureList::parse("").is_empty());
+        assert!(FailureList::parse("\n# nothing\n   \n").is_empty());
+    }
+
+    #[test]
+    fn windows_line_endings() {
+        let list = FailureList::parse("Required.A\r\nRequired.B\r\n");
+        assert!(list.contains("Required.A"));
+        assert!(list.contains("Required.B"));
+    }
+
+    #[test]
+    fn duplicates() {
+        let list = FailureList::parse("Required.A\nRequired.A\n");
+        assert_eq!(list.len(), 1);
+    }
+}
@@ -0,0 +1,350 @@
+//! Drives the suite against a testee, asserting and reporting every case.
+//!
+//! Implementations written in Rust can run the suite in-process, for
+//! example from `cargo test`, instead of building a program that speaks the
+//! stdio protocol:
+//!
+//! ```ignore
+//! use conform::assert::conformance::{ConformanceRequest, ConformanceResponse};
+//! use conform::harness::{Harness, Testee};
+//! use conform::report::tap::Tap;
+//! use conform::suite::{self, Sources};
+//!
+//! struct MyProtobuf;
+//!
+//! impl Testee for MyProtobuf {
+//!     fn handle(&mut self, request: ConformanceRequest) -> ConformanceResponse {
+//!         todo!("parse the payload and serialize it in the requested format")
+//!     }
+//! }
+//!
+//! #[test]
+//! fn conformance() {
+//!     let cases = suite::load(&Sources::default()).unwrap();
+//!     let run = Harness::new(&cases)
+//!         .run(&mut MyProtobuf, &mut Tap::new(std::io::stdout()))
+//!         .unwrap();
+//!     assert!(run.passed(), "{}", run.stats);
+//! }
+//! ```
+
+use std::time::Instant;
+
+use anyhow::Context;
+use protobuf::Message;
+
+use crate::assert::case::{self, Outcome};
+use crate::assert::conformance::{ConformanceRequest, ConformanceResponse};
+use crate::failure_list::FailureList;
+use crate::report::{CaseResult, Report};
+use crate::stats::{Stats, Verdict};
+use crate::test_case::TestCase;
+
+/// An implementation under test that answers requests in-process.
+pub trait Testee {
+    fn handle(&mut self, request: ConformanceRequest) -> ConformanceResponse;
+}
+
+impl<F> Testee for F
+where
+    F: FnMut(ConformanceRequest) -> ConformanceResponse,
+{
+    fn handle(&mut self, request: ConformanceRequest) -> ConformanceResponse {
+        self(request)
+    }
+}
+
+/// What came out of running the cases.
+pub struct Run {
+    pub stats: Stats,
+    /// Verdict of every case, by position in the suite. Cases that weren't
+    /// reached, because the testee crashed or the run exited early, have none.
+    pub verdicts: Vec<Option<Verdict>>,
+    /// Whether the testee stopped answering.
+    pub crashed: bool,
+}
+
+impl Run {
+    pub fn passed(&self) -> bool {
+        !self.crashed && !self.stats.failed()
+    }
+}
+
+pub fn matches_filter(filters: &[String], name: &str) -> bool {
+    filters.is_empty() || filters.iter().any(|f| name.contains(f))
+}
+
+/// Applies the failure list and the enforcement policy to the assertion of a
+/// case.
+pub fn judge(
+    case: &TestCase,
+    assertion: &Outcome,
+    expected_to_fail: bool,
+    enforce_recommended: bool,
+) -> Verdict {
+    if assertion.skipped() {
+        Verdict::Skipped
+    } else if assertion.passed() && expected_to_fail {
+        Verdict::UnexpectedPass
+    } else if assertion.passed() {
+        Verdict::Passed
+    } else if expected_to_fail {
+        Verdict::ExpectedFailure
+    } else if case.is_recommended() && !enforce_recommended {
+        Verdict::Warning
+    } else {
+        Verdict::Failed
+    }
+}
+
+/// Runs a suite of cases, by default every case with recommended ones only
+/// warning.
+pub struct Harness<'a> {
+    cases: &'a [TestCase],
+    failure_list: FailureList,
+    enforce_recommended: bool,
+    exit_early: bool,
+    filter: Vec<String>,
+    skip_format: Vec<String>,
+}
+
+impl<'a> Harness<'a> {
+    pub fn new(cases: &'a [TestCase]) -> Self {
+        Self {
+            cases,
+            failure_list: FailureList::default(),
+            enforce_recommended: false,
+            exit_early: false,
+            filter: Vec::new(),
+            skip_format: Vec::new(),
+        }
+    }
+
+    pub fn set_failure_list(&mut self, failure_list: FailureList) -> &mut Self {
+        self.failure_list = failure_list;
+        self
+    }
+
+    pub fn set_enforce_recommended(&mut self, enforce: bool) -> &mut Self {
+        self.enforce_recommended = enforce;
+        self
+    }
+
+    pub fn set_exit_early(&mut self, exit_early: bool) -> &mut Self {
+        self.exit_early = exit_early;
+        self
+    }
+
+    /// Only run the cases whose name contains one of the filters.
+    pub fn set_filter(&mut self, filter: Vec<String>) -> &mut Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Skip the cases with any of these input or output formats.
+    pub fn set_skip_format(&mut self, formats: Vec<String>) -> &mut Self {
+        self.skip_format = formats;
+        self
+    }
+
+    pub fn cases(&self) -> &'a [TestCase] {
+        self.cases
+    }
+
+    /// Whether the case is on the failure list.
+    pub fn expects_failure(&self, case: &TestCase) -> bool {
+        self.failure_list.contains(&case.name)
+    }
+
+    pub fn enforces_recommended(&self) -> bool {
+        self.enforce_recommended
+    }
+
+    pub fn skips(&self, case: &TestCase) -> anyhow::Result<bool> {
+        if !matches_filter(&self.filter, &case.name) {
+            return Ok(true);
+        }
+        if self.skip_format.is_empty() {
+            return Ok(false);
+        }
+        let formats = case.formats()?;
+        Ok(self
+            .skip_format
+            .iter()
+            .any(|f| formats.contains(&f.as_str())))
+    }
+
+    /// Runs every case against an in-process testee, from the start to the
+    /// end of the suite.
+    pub fn run<T, R>(&self, testee: &mut T, report: &mut R) -> anyhow::Result<Run>
+    where
+        T: Testee + ?Sized,
+        R: Report,
+    {
+        report.suite_start(self.cases);
+        let run = self.run_cases(
+            |case| {
+                let response = testee.handle(case.request()?);
+                response
+                    .write_to_bytes()
+                    .context("failed to serialize the response")
+            },
+            report,
+        )?;
+        report.suite_end(&run.stats);
+        report.finish()?;
+        Ok(run)
+    }
+
+    /// Runs every case through `send`, which returns the serialized
+    /// `ConformanceResponse` to the request of a case. An error means the
+    /// testee is gone and stops the run. Only the case events are reported,
+    /// the suite start and end are left to the caller.
+    pub fn run_cases<F, R>(&self, mut send: F, report: &mut R) -> anyhow::Result<Run>
+    where
+        F: FnMut(&TestCase) -> anyhow::Result<Vec<u8>>,
+        R: Report,
+    {
+        let mut stats = Stats::new(self.cases.len() as u32);
+        let mut verdicts: Vec<Option<Verdict>> = vec![None; self.cases.len()];
+        let mut crashed = false;
+
+        for (pos, case) in self.cases.iter().enumerate() {
+            let number = pos as u32;
+            report.case_start(number, case);
+
+            let (assertion, duration) = if self.skips(case)? {
+                (Outcome::not_run(), None)
+            } else {
+                let start = Instant::now();
+                let response = send(case);
+                let duration = start.elapsed();
+                stats.record_duration(&case.name, duration);
+
+                match response {
+                    Ok(response) => (case::assert(case, &response), Some(duration)),
+                    Err(err) => {
+                        crashed = true;
+                        (Outcome::from_error(&err), Some(duration))
+                    }
+                }
+            };
+
+            let verdict = if crashed {
+                Verdict::Failed
+            } else {
+                judge(
+                    case,
+                    &assertion,
+                    self.expects_failure(case),
+                    self.enforce_recommended,
+                )
+            };
+            report.case_finished(&CaseResult {
+                number,
+                case,
+                outcome: &assertion,
+                verdict,
+                duration,
+            });
+
+            stats.record(&case.level, verdict);
+            verdicts[pos] = Some(verdict);
+
+            if crashed || (verdict == Verdict::Failed && self.exit_early) {
+                break;
+            }
+        }
+
+        stats.calculate();
+        Ok(Run {
+            stats,
+            verdicts,
+            crashed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::conformance::{conformance_request, conformance_response};
+    use crate::report::tap::Tap;
+    use crate::test_case::{AssertBy, Level};
+
+    /// A case expecting a parse error, told apart by its protobuf payload.
+    fn case(name: &str, tag: u8) -> TestCase {
+        let mut request = ConformanceRequest::new();
+        request.message_type = String::from("protobuf_test_messages.proto3.TestAllTypesProto3");
+        request.payload = Some(conformance_request::Payload::ProtobufPayload(vec![tag]));
+        TestCase {
+            name: name.to_string(),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: request.write_to_bytes().unwrap(),
+            assert_by: AssertBy::ParseError,
+        }
+    }
+
+    fn testee(request: ConformanceRequest) -> ConformanceResponse {
+        use conformance_response::Result;
+        let mut response = ConformanceResponse::new();
+        response.result = Some(match request.payload {
+            Some(conformance_request::Payload::ProtobufPayload(tag)) if tag == [1] => {
+                Result::ParseError(String::from("bad payload"))
+            }
+            Some(conformance_request::Payload::ProtobufPayload(tag)) if tag == [3] => {
+                Result::Skipped(String::from("not supported"))
+            }
+            _ => Result::ProtobufPayload(Vec::new()),
+        });
+        response
+    }
+
+    #[test]
+    fn runs_a_closure_testee() {
+        let cases = [
+            case("Required.Pass", 1),
+            case("Required.Fail", 2),
+            case("Required.Skip", 3),
+            case("Required.Listed", 4),
+        ];
+        let mut harness = Harness::new(&cases);
+        harness.set_failure_list(FailureList::parse("Required.Listed\n"));
+
+        let mut calls = 0;
+        let mut closure = |request: ConformanceRequest| {
+            calls += 1;
+            testee(request)
+        };
+        let mut tap = Tap::new(Vec::new());
+        let run = harness.run(&mut closure, &mut tap).unwrap();
+        assert_eq!(calls, 4);
+        assert_eq!(
+            run.verdicts,
+            [
+                Some(Verdict::Passed),
+                Some(Verdict::Failed),
+                Some(Verdict::Skipped),
+                Some(Verdict::ExpectedFailure),
+            ]
+        );
+        assert!(!run.crashed);
+        assert!(!run.passed());
+
+        let report = String::from_utf8(tap.into_inner()).unwrap();
+        assert!(report.contains("ok 0 - Required.Pass\n"), "{}", report);
+        assert!(report.contains("not ok 1 - Required.Fail\n"), "{}", report);
+        assert!(
+            report.contains("ok 2 - Required.Skip # SKIP\n"),
+            "{}",
+            report
+        );
+        assert!(report.contains("not ok 3 - Required.Listed # TODO expected failure\n"));
+
+        harness.set_filter(vec![String::from("Pass"), String::from("Skip")]);
+        let run = harness.run(&mut testee, &mut Tap::new(Vec::new())).unwrap();
+        assert_eq!(run.stats.passed, 1);
+        assert!(run.passed());
+    }
+}
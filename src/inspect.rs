@@ -0,0 +1,169 @@
+use anyhow::Context;
+use protobuf::text_format;
+
+use crate::assert::conformance::{
+    conformance_request::Payload, conformance_response::Result, ConformanceRequest,
+    ConformanceResponse,
+};
+use crate::assert::differ;
+use crate::assert::message::TestMessage;
+use crate::test_case::{AssertBy, TestCase};
+
+/// Renders a request with its input payload decoded in its native format.
+pub fn describe_request(request: &ConformanceRequest) -> String {
+    let payload = match &request.payload {
+        Some(Payload::ProtobufPayload(buf)) => {
+            match TestMessage::from_protobuf_payload(&request.message_type, buf) {
+                Ok(message) => format!("protobuf payload:\n{}", message.to_text()),
+                Err(e) => format!(
+                    "protobuf payload that doesn't parse ({}):\n{}",
+                    e,
+                    hexdump(buf)
+                ),
+            }
+        }
+        Some(Payload::JsonPayload(json)) => format!("json payload:\n{}", json),
+        Some(Payload::TextPayload(text)) => format!("text payload:\n{}", text),
+        Some(Payload::JspbPayload(jspb)) => format!("jspb payload:\n{}", jspb),
+        None => String::from("no payload"),
+    };
+
+    let mut rest = request.clone();
+    rest.payload = None;
+    format!("{}{}", text_format::print_to_string_pretty(&rest), payload)
+}
+
+/// Renders a response with its payload decoded into the test message.
+pub fn describe_response(request: &ConformanceRequest, response: &ConformanceResponse) -> String {
+    let message_type = request.message_type.as_str();
+    match &response.result {
+        Some(Result::ProtobufPayload(buf)) => {
+            match TestMessage::from_protobuf_payload(message_type, buf) {
+                Ok(message) => format!("protobuf payload:\n{}", message.to_text()),
+                Err(e) => format!(
+                    "protobuf payload that doesn't parse ({}):\n{}",
+                    e,
+                    hexdump(buf)
+                ),
+            }
+        }
+        Some(Result::JsonPayload(json)) => format!("json payload:\n{}", json),
+        Some(Result::TextPayload(text)) => match TestMessage::from_text(message_type, text) {
+            Ok(message) => format!("text payload:\n{}", message.to_text()),
+            Err(e) => format!("text payload that doesn't parse ({:#}):\n{}", e, text),
+        },
+        Some(result) => result.to_string(),
+        None => String::from("no result"),
+    }
+}
+
+/// Describes what the response to a case is expected to be.
+pub fn expected(case: &TestCase) -> anyhow::Result<String> {
+    let expected = match &case.assert_by {
+        AssertBy::Equivalence(equivalent, same_wire) => {
+            let request = case.request()?;
+            let message = TestMessage::from_protobuf_payload(&request.message_type, equivalent)
+                .context("failed to parse the equivalent message")?;
+            let mut expected = format!("# {}\n", request.message_type);
+            if *same_wire {
+                expected.push_str("# the response must have the same wire format\n");
+            }
+            expected.push_str(&message.to_text());
+            expected
+        }
+        AssertBy::ParseError => String::from("a parse error\n"),
+        AssertBy::SerializationError => String::from("a serialize error\n"),
+        AssertBy::Validator(None) => {
+            String::from("a json payload that passes the validator of the case\n")
+        }
+        AssertBy::Validator(Some(checks)) => {
+            let mut expected = String::from("a json payload that passes the checks\n");
+            for check in checks {
+                expected.push_str(&format!("{}\n", serde_json::to_string(check)?));
+            }
+            expected
+        }
+    };
+    Ok(expected)
+}
+
+/// Unified diff between the message in the response and the expected one, for
+/// cases asserted by equivalence.
+pub fn diff(
+    case: &TestCase,
+    response: &ConformanceResponse,
+    context: usize,
+) -> anyhow::Result<Option<String>> {
+    let AssertBy::Equivalence(equivalent, _) = &case.assert_by else {
+        return Ok(None);
+    };
+    let request = case.request()?;
+    let actual = match &response.result {
+        Some(Result::ProtobufPayload(buf)) => {
+            TestMessage::from_protobuf_payload(&request.message_type, buf)?
+        }
+        Some(Result::JsonPayload(json)) => {
+            TestMessage::from_json_payload(&request.message_type, json)?
+        }
+        Some(Result::TextPayload(text)) => TestMessage::from_text(&request.message_type, text)?,
+        _ => return Ok(None),
+    };
+    let expected = TestMessage::from_protobuf_payload(&request.message_type, equivalent)?;
+    let (_, diff) = differ::diff(&actual.to_text(), &expected.to_text(), context);
+    Ok(Some(diff))
+}
+
+/// Classic hex dump, 16 bytes per line with the printable characters aside.
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:08x}  {:<47}  {}\n",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_case::Level;
+    use protobuf::Message;
+
+    #[test]
+    fn text_payloads_are_decoded_and_diffed() {
+        let message_type = "protobuf_test_messages.proto3.TestAllTypesProto3";
+        let mut request = ConformanceRequest::new();
+        request.message_type = message_type.to_string();
+        request.payload = Some(Payload::ProtobufPayload(vec![8, 1]));
+        let case = TestCase {
+            name: String::from("Custom"),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: request.write_to_bytes().unwrap(),
+            assert_by: AssertBy::Equivalence(vec![8, 1], false),
+        };
+        let mut response = ConformanceResponse::new();
+        response.result = Some(Result::TextPayload(String::from("optional_int32: 2")));
+
+        let described = describe_response(&request, &response);
+        assert_eq!(described, "text payload:\noptional_int32: 2\n");
+        let diff = diff(&case, &response, 3).unwrap().expect("a diff");
+        assert!(diff.contains("-optional_int32: 2"), "{}", diff);
+        assert!(diff.contains("+optional_int32: 1"), "{}", diff);
+    }
+}
@@ -1,6 +1,20 @@
+#[cfg(feature = "extract")]
 pub mod ffi;
 pub mod report;
 pub mod test_case;
 pub mod runner;
+pub mod transport;
 pub mod assert;
-pub mod stats;
\ No newline at end of file
+pub mod stats;
+pub mod failure_list;
+pub mod config;
+pub mod matrix;
+pub mod diff;
+pub mod recording;
+pub mod repro;
+pub mod inspect;
+pub mod suite;
+pub mod custom;
+pub mod harness;
+#[cfg(feature = "libtest")]
+pub mod libtest;
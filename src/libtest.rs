@@ -0,0 +1,90 @@
+//! Runs every case as its own libtest test, so `cargo test` and nextest
+//! filter, parallelize and report the suite like any other tests. The test
+//! target needs `harness = false`:
+//!
+//! ```toml
+//! [[test]]
+//! name = "conformance"
+//! harness = false
+//! ```
+//!
+//! ```ignore
+//! use conform::harness::Harness;
+//! use conform::suite::{self, Sources};
+//!
+//! fn main() {
+//!     let cases = suite::load(&Sources::default()).unwrap();
+//!     conform::libtest::run(&Harness::new(&cases), || MyProtobuf).exit();
+//! }
+//! ```
+//!
+//! after which `cargo test --test conformance -- Required.Proto3.JsonInput`
+//! only runs the matching cases.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use libtest_mimic::{Arguments, Conclusion, Trial};
+use protobuf::Message;
+
+use crate::assert::case::{self, Outcome};
+use crate::harness::{judge, Harness, Testee};
+use crate::stats::Verdict;
+use crate::test_case::TestCase;
+
+/// One test per case of the harness. Tests run in parallel, so every test
+/// gets a fresh testee from `testee`. Cases skipped by the harness are
+/// ignored, and like on the command line, only failures and unexpected passes
+/// fail a test.
+pub fn trials<T, F>(harness: &Harness, testee: F) -> anyhow::Result<Vec<Trial>>
+where
+    T: Testee,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let testee = Arc::new(testee);
+    let mut trials = Vec::with_capacity(harness.cases().len());
+    for case in harness.cases() {
+        let ignored = harness.skips(case)?;
+        let testee = Arc::clone(&testee);
+        let name = case.name.clone();
+        let case = case.clone();
+        // tests can't borrow the harness, so the policy is copied
+        let expected_to_fail = harness.expects_failure(&case);
+        let enforce_recommended = harness.enforces_recommended();
+        trials.push(
+            Trial::test(name, move || {
+                let outcome = run_case(&case, &mut testee())?;
+                match judge(&case, &outcome, expected_to_fail, enforce_recommended) {
+                    Verdict::Failed => Err(format!("{}\n{}", case, outcome).into()),
+                    Verdict::UnexpectedPass => {
+                        Err("passed unexpectedly, remove it from the failure list".into())
+                    }
+                    _ => Ok(()),
+                }
+            })
+            // no kind, as it would prefix the names nextest lists
+            .with_ignored_flag(ignored),
+        );
+    }
+    Ok(trials)
+}
+
+fn run_case<T: Testee>(case: &TestCase, testee: &mut T) -> anyhow::Result<Outcome> {
+    let response = testee
+        .handle(case.request()?)
+        .write_to_bytes()
+        .context("failed to serialize the response")?;
+    Ok(case::assert(case, &response))
+}
+
+/// Runs the cases of the harness as tests, taking the arguments from the
+/// command line of the test binary.
+pub fn run<T, F>(harness: &Harness, testee: F) -> Conclusion
+where
+    T: Testee,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let args = Arguments::from_args();
+    let trials = trials(harness, testee).expect("failed to prepare the conformance tests");
+    libtest_mimic::run(&args, trials)
+}
@@ -1,126 +1,1423 @@
-use clap::Parser;
-use conform::report::{get_output, tap::Tap, Report};
+use anyhow::{anyhow, Context};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use conform::assert::case::{Case, Outcome};
+use conform::assert::conformance::{ConformanceRequest, ConformanceResponse};
+use conform::assert::message::TestMessage;
+use conform::config::{self, Config};
+use conform::failure_list::FailureList;
+use conform::harness::{self, Harness};
+use conform::matrix::Matrix;
+use conform::report::fanout::{Fanout, Kind, Spec};
+use conform::report::terminal;
+use conform::report::{CaseResult, Report};
 use conform::runner::Runner;
-use conform::stats::Stats;
-use conform::test_case::TestCase;
-use conform::{assert, ffi};
-use std::borrow::BorrowMut;
+use conform::stats::{self, Stats, Verdict};
+use conform::suite::{self, Sources};
+use conform::test_case::{self, AssertBy, Level, TestCase};
+use conform::{assert, diff, inspect, recording, repro};
+use protobuf::Message;
+use serde::Serialize;
 use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
-#[command(author = "thesayyn", version = "0.0.0", about, long_about = None)]
+#[command(
+    author = "thesayyn",
+    version = "0.0.0",
+    about,
+    long_about = None,
+    after_help = indoc::indoc! {"
+        Exit codes:
+          0  every case passed, or failed as expected by the failure list
+          1  one or more cases failed or disagreed, or a minimum score was not reached
+          2  one or more cases in the failure list passed unexpectedly
+          3  the testee could not be spawned or crashed during the run
+          4  conform itself ran into an error, such as invalid arguments or an unreadable file
+    "}
+)]
 struct Conform {
-    #[arg(short, long, help = "conformance runner program")]
-    program: String,
-    #[arg(short, long, help = "where to write the report", default_value_t = String::from("-"))]
-    output: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "run from a suite file written by `conform export-suite` instead of extracting it"
+    )]
+    suite: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "directory of custom cases to run alongside the suite"
+    )]
+    cases_dir: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "json validators by case name, overriding the built-in ones"
+    )]
+    validators: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "configuration file, defaults to conform.toml in the working directory if present"
+    )]
+    config: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "conformance runner program, as a path or `name=path`. can be repeated to run several programs"
+    )]
+    program: Vec<String>,
+    #[arg(
+        long,
+        help = "run several programs in parallel",
+        overrides_with = "no_parallel"
+    )]
+    parallel: bool,
+    #[arg(
+        long,
+        help = "run the programs one after the other, even if the config says otherwise"
+    )]
+    no_parallel: bool,
+    #[arg(
+        long,
+        help = "where to write the per-case outcome of every program as a markdown table"
+    )]
+    matrix: Option<String>,
+    #[arg(
+        long = "program-arg",
+        help = "argument to pass to the program, can be repeated",
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+    #[arg(last = true, help = "arguments to pass to the program")]
+    trailing_args: Vec<String>,
+    #[arg(long, help = "working directory of the program")]
+    program_cwd: Option<String>,
+    #[arg(short, long, help = "where to write the report [default: -]")]
+    output: Option<String>,
+    #[arg(
+        long,
+        help = "report to produce as kind[=path], where kind is tap, junit or terminal and path defaults to stdout. can be repeated, replaces --output"
+    )]
+    reporter: Vec<Spec>,
 
     #[arg(
         long,
         help = "stop the test runner at first error",
-        default_value_t = false
+        overrides_with = "no_exit_early"
     )]
     exit_early: bool,
+    #[arg(long, help = "run every case, even if the config says to exit early")]
+    no_exit_early: bool,
 
     #[arg(long, help = "generate stats in json format")]
     json_stats: Option<String>,
 
+    #[arg(
+        long,
+        help = "enforce recommended test",
+        overrides_with = "no_enforce_recommended"
+    )]
+    enforce_recommended: bool,
+    #[arg(
+        long,
+        help = "only warn about recommended tests, even if the config enforces them"
+    )]
+    no_enforce_recommended: bool,
+
+    #[arg(
+        long,
+        help = "where to write the stderr from runner. possible values are either `ignore` or a file path. [default: ignore]"
+    )]
+    runner_stderr: Option<String>,
+
+    #[arg(long, help = "environment variables for the runner.", value_parser)]
+    runner_env: Vec<String>,
+
+    #[arg(long, help = "list the N slowest cases at the end of the run")]
+    slowest: Option<usize>,
+
+    #[arg(long, help = "file listing the cases that are expected to fail")]
+    failure_list: Option<String>,
+
+    #[arg(
+        long,
+        help = "minimum score (0-100) the required cases must reach",
+        value_parser = parse_score
+    )]
+    min_required_score: Option<f32>,
+
+    #[arg(
+        long,
+        help = "minimum score (0-100) the recommended cases must reach",
+        value_parser = parse_score
+    )]
+    min_recommended_score: Option<f32>,
+
+    #[arg(
+        long,
+        help = "only run the cases whose name contains one of the filters"
+    )]
+    filter: Vec<String>,
+
+    #[arg(
+        long,
+        help = "skip the cases that take or produce the given format",
+        value_parser = test_case::FORMATS
+    )]
+    skip_format: Vec<String>,
+
+    #[arg(long, help = "seconds to wait for the response of a single case")]
+    timeout: Option<f64>,
+
+    #[arg(
+        long,
+        help = "record every exchange with the program to a file, see `conform replay`"
+    )]
+    record: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "compare the responses of a program against a reference implementation")]
+    Diff(DiffArgs),
+    #[command(about = "re-assert a session recorded with --record, without running the program")]
+    Replay(ReplayArgs),
+    #[command(about = "write out the files needed to reproduce a case outside of conform")]
+    Repro(ReproArgs),
+    #[command(about = "run a single case and print a detailed breakdown of the exchange")]
+    RunOne(RunOneArgs),
+    #[command(about = "list the cases of the suite")]
+    List(ListArgs),
+    #[command(about = "decode the request and the expected response of a case")]
+    Show(ShowArgs),
+    #[command(about = "write the suite to a file that can be used with --suite")]
+    ExportSuite(ExportSuiteArgs),
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    #[arg(short, long, help = "program under test")]
+    program: String,
+    #[arg(short, long, help = "reference implementation to compare against")]
+    reference: String,
+    #[arg(short, long, help = "where to write the report", default_value_t = String::from("-"))]
+    output: String,
+    #[arg(
+        long,
+        help = "report to produce as kind[=path], where kind is tap, junit or terminal and path defaults to stdout. can be repeated, replaces --output"
+    )]
+    reporter: Vec<Spec>,
+
+    #[arg(
+        long,
+        help = "only compare the cases whose name contains one of the filters"
+    )]
+    filter: Vec<String>,
+
+    #[arg(long, help = "environment variables for both programs.", value_parser)]
+    runner_env: Vec<String>,
+
+    #[arg(long, help = "seconds to wait for the response of a single case")]
+    timeout: Option<f64>,
+
+    #[arg(long, help = "unchanged lines shown around each difference", default_value_t = assert::differ::DEFAULT_CONTEXT)]
+    context: usize,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    #[arg(help = "recording to replay")]
+    recording: String,
+    #[arg(short, long, help = "where to write the report", default_value_t = String::from("-"))]
+    output: String,
+    #[arg(
+        long,
+        help = "report to produce as kind[=path], where kind is tap, junit or terminal and path defaults to stdout. can be repeated, replaces --output"
+    )]
+    reporter: Vec<Spec>,
+
+    #[arg(long, help = "file listing the cases that are expected to fail")]
+    failure_list: Option<String>,
+
     #[arg(long, help = "enforce recommended test", default_value_t = false)]
     enforce_recommended: bool,
+}
 
+#[derive(Args)]
+struct ReproArgs {
+    #[arg(help = "name of the case to reproduce")]
+    case: String,
     #[arg(
+        short,
         long,
-        help = "where to write the stderr from runner. possible values are either `ignore` or a file path.",
-        default_value_t = String::from("ignore")
+        help = "directory to write the files to, defaults to the case name"
+    )]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct RunOneArgs {
+    #[arg(help = "name of the case to run")]
+    case: String,
+
+    #[arg(short, long, help = "conformance runner program")]
+    program: String,
+    #[arg(
+        long = "program-arg",
+        help = "argument to pass to the program, can be repeated",
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+    #[arg(last = true, help = "arguments to pass to the program")]
+    trailing_args: Vec<String>,
+    #[arg(long, help = "working directory of the program")]
+    program_cwd: Option<String>,
+
+    #[arg(
+        long,
+        help = "where to write the stderr from runner. possible values are `inherit`, `ignore` or a file path.",
+        default_value_t = String::from("inherit")
     )]
     runner_stderr: String,
 
     #[arg(long, help = "environment variables for the runner.", value_parser)]
     runner_env: Vec<String>,
+
+    #[arg(long, help = "seconds to wait for the response")]
+    timeout: Option<f64>,
+
+    #[arg(long, help = "unchanged lines shown around each difference", default_value_t = assert::differ::DEFAULT_CONTEXT)]
+    context: usize,
+
+    #[arg(
+        long,
+        help = "wait for enter before sending the case, to attach a debugger to the program",
+        default_value_t = false
+    )]
+    pause: bool,
 }
 
-fn parse_env(v: &String) -> (String, String) {
-    let mut kv = v.split("=").into_iter();
-    (
-        kv.next().expect("failed to parse --runner-env").to_string(),
-        kv.next().expect("").to_string(),
-    )
+#[derive(Args)]
+struct ListArgs {
+    #[arg(
+        long,
+        help = "only list the cases whose name contains one of the filters"
+    )]
+    filter: Vec<String>,
+    #[arg(long, help = "print the cases as json", default_value_t = false)]
+    json: bool,
 }
 
-fn main() {
-    let cli = Conform::parse();
+#[derive(Args)]
+struct ShowArgs {
+    #[arg(help = "name of the case to show")]
+    case: String,
+}
 
-    let mut binding = ffi::extract_suite();
-    let cases = binding.as_mut().unwrap();
-    let cases_len = cases.len() as u32;
+#[derive(Args)]
+struct ExportSuiteArgs {
+    #[arg(help = "file to write the suite to")]
+    file: String,
+}
 
-    let mut runner = Runner::new(&cli.program);
-    let mut tap = Tap::new(get_output(&cli.output));
-    let mut stats = Stats::new(cases_len);
+#[derive(Serialize)]
+struct Listing<'a> {
+    name: &'a str,
+    level: String,
+    syntax: &'a str,
+    assertion: &'a str,
+}
 
-    runner.set_env_all(cli.runner_env.iter().map(parse_env).collect());
-    runner
-        .set_stderr(cli.runner_stderr)
-        .expect("failed to set stderr for the runner");
+impl Conform {
+    /// Fills in everything that wasn't given on the command line from the
+    /// configuration file.
+    fn merge(&mut self, config: Config) {
+        if self.program.is_empty() {
+            self.program = config.program.into_iter().collect();
+            self.program.extend(
+                config
+                    .programs
+                    .into_iter()
+                    .map(|(name, path)| format!("{}={}", name, path)),
+            );
+        }
+        self.matrix = self.matrix.take().or(config.matrix);
+        self.parallel = switch(self.parallel, self.no_parallel, config.parallel);
+        self.output = self.output.take().or(config.output);
+        self.runner_stderr = self.runner_stderr.take().or(config.runner_stderr);
+        self.json_stats = self.json_stats.take().or(config.json_stats);
+        self.failure_list = self.failure_list.take().or(config.failure_list);
+        self.timeout = self.timeout.or(config.timeout);
+        self.record = self.record.take().or(config.record);
+        self.suite = self.suite.take().or(config.suite);
+        self.cases_dir = self.cases_dir.take().or(config.cases_dir);
+        self.validators = self.validators.take().or(config.validators);
+        self.slowest = self.slowest.or(config.slowest);
+        self.min_required_score = self.min_required_score.or(config.min_required_score);
+        self.min_recommended_score = self.min_recommended_score.or(config.min_recommended_score);
+        self.exit_early = switch(self.exit_early, self.no_exit_early, config.exit_early);
+        self.enforce_recommended = switch(
+            self.enforce_recommended,
+            self.no_enforce_recommended,
+            config.enforce_recommended,
+        );
+
+        if self.filter.is_empty() {
+            self.filter = config.filter;
+        }
+        if self.skip_format.is_empty() {
+            self.skip_format = config.skip_format;
+        }
+        if self.reporter.is_empty() {
+            self.reporter = config.reporters;
+        }
 
-    tap.plan(0, cases_len);
-    tap.diagnostic("conform - a better conformance test runner");
+        // env from the command line is applied last, so it overrides the file.
+        let mut env: Vec<String> = config
+            .env
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        env.append(&mut self.runner_env);
+        self.runner_env = env;
 
-    let spawn = runner.spawn();
-    if spawn.is_err() {
-        tap.diagnostic(format!("{}", spawn.err().unwrap()));
+        self.args.append(&mut self.trailing_args);
+        if self.args.is_empty() {
+            self.args = config.args;
+        }
+        self.program_cwd = self.program_cwd.take().or(config.program_cwd);
+    }
+}
+
+/// Resolves a flag that is turned on with `--flag` and off with `--no-flag`,
+/// falling back to the configuration when neither is given.
+fn switch(on: bool, off: bool, config: Option<bool>) -> bool {
+    if on {
+        true
+    } else if off {
+        false
     } else {
-        tap.diagnostic(format!("{} is running now", cli.program));
+        config.unwrap_or(false)
     }
+}
 
-    for (pos, mut raw) in cases.iter_mut().borrow_mut().enumerate() {
-        let num = pos as u32;
-        let case = TestCase::from(&mut raw);
+/// A program under test, named for the reports.
+struct Program {
+    name: String,
+    path: String,
+}
 
-        let case_response = runner.send_case(&case);
+impl From<&String> for Program {
+    /// Parses `name=path`, or just a path if what comes before the first `=`
+    /// isn't a name, so paths containing `=` still work.
+    fn from(value: &String) -> Self {
+        match value.split_once('=') {
+            Some((name, path)) if config::is_program_name(name) => Self {
+                name: name.to_string(),
+                path: path.to_string(),
+            },
+            _ => Self {
+                name: Path::new(value)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or(value.clone()),
+                path: value.clone(),
+            },
+        }
+    }
+}
 
-        if case_response.is_err() {
-            tap.not_ok(num, &case.name);
-            tap.diagnostic(format!("{}", case_response.err().unwrap()));
-            stats.failed += 1;
+/// Result of running the suite against a single program.
+struct ProgramRun {
+    verdicts: Vec<Option<Verdict>>,
+    exit: Exit,
+}
+
+/// Outcome of the whole run, mapped to the process exit code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    Success = 0,
+    Failures = 1,
+    UnexpectedPasses = 2,
+    TesteeCrashed = 3,
+    InternalError = 4,
+}
+
+impl Exit {
+    fn from_stats(crashed: bool, stats: &Stats, below_threshold: bool) -> Exit {
+        if crashed {
+            Exit::TesteeCrashed
+        } else if stats.failed() || below_threshold {
+            Exit::Failures
+        } else if stats.unexpected_passes > 0 {
+            Exit::UnexpectedPasses
+        } else {
+            Exit::Success
+        }
+    }
+
+    /// Picks the exit code that says the most about two runs.
+    fn worst(self, other: Exit) -> Exit {
+        let rank = |e: Exit| match e {
+            Exit::Success => 0,
+            Exit::UnexpectedPasses => 1,
+            Exit::Failures => 2,
+            Exit::TesteeCrashed => 3,
+            Exit::InternalError => 4,
+        };
+        if rank(other) > rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+/// Gives every program its own file when several of them run, by putting the
+/// program name before the extension.
+fn per_program(path: &str, name: &str, multiple: bool) -> String {
+    if !multiple || path == "-" || path == "ignore" {
+        return path.to_string();
+    }
+    let p = Path::new(path);
+    let stem = p.file_stem().unwrap_or_default().to_string_lossy();
+    let file = match p.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, name, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, name),
+    };
+    p.with_file_name(file).to_string_lossy().to_string()
+}
+
+fn find_case(cases: Vec<TestCase>, name: &str) -> anyhow::Result<TestCase> {
+    cases
+        .into_iter()
+        .find(|c| c.name == name)
+        .ok_or(anyhow!("no case named {} in the suite", name))
+}
+
+fn print_case(case: &TestCase, request: &ConformanceRequest) -> anyhow::Result<()> {
+    println!("== case");
+    println!("name: {}", case.name);
+    println!("level: {}", case.level.to_string());
+    println!("syntax: {}", case.syntax);
+    println!("assertion: {}", case.assert_by.kind());
+    println!();
+    println!("== request");
+    println!("{}", inspect::describe_request(request));
+    println!("== expected");
+    println!("{}", inspect::expected(case)?);
+    Ok(())
+}
+
+/// Parses the command line. Only the global flags, such as `--suite`, go with
+/// a subcommand, the rest are about running the suite.
+fn parse_args<I, T>(args: I) -> Result<Conform, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let mut command = Conform::command();
+    let matches = command.try_get_matches_from_mut(args)?;
+    if let Some((subcommand, _)) = matches.subcommand() {
+        let misplaced = command.get_arguments().find(|arg| {
+            !arg.is_global_set()
+                && matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        });
+        if let Some(arg) = misplaced {
+            let flag = arg.get_long().unwrap_or(arg.get_id().as_str());
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                format!("--{} can't be used with the {} command", flag, subcommand),
+            ));
+        }
+    }
+    Conform::from_arg_matches(&matches)
+}
+
+fn parse_score(v: &str) -> anyhow::Result<f32> {
+    stats::check_score(v.parse()?)
+}
+
+/// Splits `--runner-env` values at their first `=`, so values may contain
+/// more of them.
+fn parse_env(values: &[String]) -> anyhow::Result<Vec<(String, String)>> {
+    values
+        .iter()
+        .map(|v| match v.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(anyhow!("invalid --runner-env {}, expected KEY=VALUE", v)),
+        })
+        .collect()
+}
+
+fn main() -> ExitCode {
+    let mut cli = match parse_args(std::env::args_os()) {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            // --help and --version are printed to stdout and aren't errors
+            return if err.use_stderr() {
+                Exit::InternalError.into()
+            } else {
+                Exit::Success.into()
+            };
+        }
+    };
+    let (suite, cases_dir, validators) = (
+        cli.suite.clone(),
+        cli.cases_dir.clone(),
+        cli.validators.clone(),
+    );
+    let sources = &Sources {
+        suite: suite.as_deref(),
+        cases_dir: cases_dir.as_deref(),
+        validators: validators.as_deref(),
+    };
+
+    let result = match cli.command.take() {
+        Some(Command::Diff(args)) => run_diff(args, sources),
+        Some(Command::Replay(args)) => run_replay(args, sources),
+        Some(Command::Repro(args)) => run_repro(args, sources),
+        Some(Command::RunOne(args)) => run_one(args, sources),
+        Some(Command::List(args)) => run_list(args, sources),
+        Some(Command::Show(args)) => run_show(args, sources),
+        Some(Command::ExportSuite(args)) => run_export_suite(args),
+        None => run(cli),
+    };
+
+    match result {
+        Ok(exit) => exit.into(),
+        Err(err) => {
+            eprintln!("conform: {:#}", err);
+            Exit::InternalError.into()
+        }
+    }
+}
+
+fn run(mut cli: Conform) -> anyhow::Result<Exit> {
+    let config = match &cli.config {
+        Some(path) => Some(Config::load(path)?),
+        None => Config::discover()?,
+    };
+    cli.merge(config.unwrap_or_default());
+
+    if cli.program.is_empty() {
+        return Err(anyhow!(
+            "no program given, pass --program or set it in conform.toml"
+        ));
+    }
+    let programs: Vec<Program> = cli.program.iter().map(Program::from).collect();
+    let multiple = programs.len() > 1;
+
+    let failure_list = match &cli.failure_list {
+        Some(path) => FailureList::load(path)?,
+        None => FailureList::default(),
+    };
+
+    let cases = suite::load(&Sources {
+        suite: cli.suite.as_deref(),
+        cases_dir: cli.cases_dir.as_deref(),
+        validators: cli.validators.as_deref(),
+    })?;
+
+    let mut harness = Harness::new(&cases);
+    harness
+        .set_failure_list(failure_list)
+        .set_enforce_recommended(cli.enforce_recommended)
+        .set_exit_early(cli.exit_early)
+        .set_filter(cli.filter.clone())
+        .set_skip_format(cli.skip_format.clone());
+
+    let specs = reporter_specs(
+        &cli.reporter,
+        cli.output.as_deref().unwrap_or("-"),
+        multiple,
+    )?;
+    if multiple && specs.iter().any(|spec| spec.kind == Kind::Terminal) {
+        return Err(anyhow!(
+            "the terminal reporter only works with a single program"
+        ));
+    }
+
+    let run_one = |program: &Program| -> anyhow::Result<ProgramRun> {
+        // reports of several programs to stdout are buffered, so they don't
+        // interleave when they run in parallel.
+        let mut fanout = Fanout::default();
+        for spec in &specs {
+            let path = per_program(&spec.path, &program.name, multiple);
+            fanout.open(spec, &path, &program.name, multiple)?;
+        }
+        let run = run_program(&cli, program, &cases, &harness, &mut fanout);
+        fanout.finish()?;
+        run
+    };
+
+    let runs: Vec<ProgramRun> = if cli.parallel && multiple {
+        thread::scope(|s| {
+            let handles: Vec<_> = programs
+                .iter()
+                .map(|program| s.spawn(|| run_one(program)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("program run panicked"))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?
+    } else {
+        programs
+            .iter()
+            .map(run_one)
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+
+    if let Some(path) = &cli.matrix {
+        let mut matrix = Matrix::new(&cases);
+        for (program, run) in programs.iter().zip(runs.iter()) {
+            matrix.add(&program.name, &run.verdicts);
+        }
+        fs::write(path, matrix.to_markdown()).context("failed to write the matrix")?;
+    }
+
+    Ok(runs
+        .iter()
+        .fold(Exit::Success, |exit, run| exit.worst(run.exit)))
+}
+
+/// The reporters asked for with `--reporter`, or else a single one writing
+/// to `output`: the terminal reporter when that is a terminal and only one
+/// program runs, TAP otherwise.
+fn reporter_specs(reporter: &[Spec], output: &str, multiple: bool) -> anyhow::Result<Vec<Spec>> {
+    if !reporter.is_empty() {
+        // a single program reports to stdout as it runs, so two reporters
+        // there would interleave
+        if !multiple && reporter.iter().filter(|spec| spec.path == "-").count() > 1 {
+            return Err(anyhow!("only one reporter can write to stdout"));
+        }
+        return Ok(reporter.to_vec());
+    }
+    let kind = if !multiple && output == "-" && terminal::is_terminal() {
+        Kind::Terminal
+    } else {
+        Kind::Tap
+    };
+    Ok(vec![Spec {
+        kind,
+        path: output.to_string(),
+    }])
+}
+
+/// Opens the reporters of a command that has a single report, where `name`
+/// names what is reported on.
+fn open_reporters(reporter: &[Spec], output: &str, name: &str) -> anyhow::Result<Fanout> {
+    let mut fanout = Fanout::default();
+    for spec in reporter_specs(reporter, output, false)? {
+        fanout.open(&spec, &spec.path, name, false)?;
+    }
+    Ok(fanout)
+}
+
+fn run_program<R: Report>(
+    cli: &Conform,
+    program: &Program,
+    cases: &[TestCase],
+    harness: &Harness,
+    report: &mut R,
+) -> anyhow::Result<ProgramRun> {
+    let multiple = cli.program.len() > 1;
+    let runner_stderr = per_program(
+        cli.runner_stderr.as_deref().unwrap_or("ignore"),
+        &program.name,
+        multiple,
+    );
+
+    let mut runner = Runner::new(&program.path);
+
+    runner.set_env_all(parse_env(&cli.runner_env)?);
+    runner.set_args(&cli.args);
+    if let Some(cwd) = &cli.program_cwd {
+        runner.set_cwd(cwd);
+    }
+    runner
+        .set_stderr(runner_stderr)
+        .context("failed to set stderr for the runner")?;
+    runner.set_timeout(cli.timeout.map(Duration::from_secs_f64));
+    if let Some(path) = &cli.record {
+        runner.set_record(&per_program(path, &program.name, multiple))?;
+    }
+
+    report.suite_start(cases);
+    report.diagnostic("conform - a better conformance test runner");
+
+    let run = match runner.spawn() {
+        Ok(_) => {
+            report.diagnostic(&format!("{} is running now", program.path));
+            harness.run_cases(|case| runner.send_case(case), report)?
+        }
+        Err(err) => {
+            // the first case to run fails with the reason, so that every
+            // report shows the run as failed rather than empty.
+            let reason = format!("{:#}", err);
+            report.diagnostic(&reason);
+            let mut run = harness.run_cases(|_| Err(anyhow!(reason.clone())), report)?;
+            run.crashed = true;
+            run
+        }
+    };
+    let (stats, verdicts, crashed) = (run.stats, run.verdicts, run.crashed);
+
+    // a program that crashed may fail to stop, which changes nothing about
+    // the run.
+    if let Err(err) = runner.kill() {
+        report.diagnostic(&format!("failed to stop {}: {}", program.path, err));
+    }
+
+    if let Some(n) = cli.slowest {
+        report.diagnostic(&format!("{} slowest cases:", n));
+        for (name, duration) in stats.slowest(n) {
+            report.diagnostic(&format!(
+                "{:.2}ms {}",
+                duration.as_secs_f64() * 1000.0,
+                name
+            ));
+        }
+    }
+
+    if let Some(path) = &cli.json_stats {
+        let json = stats
+            .json()
+            .context("failed to serialize stats into json")?;
+        fs::write(per_program(path, &program.name, multiple), json)
+            .context("failed to write json stats")?;
+    }
+
+    let mut below_threshold = false;
+    for (label, level, min) in [
+        ("required", &stats.required, cli.min_required_score),
+        ("recommended", &stats.recommended, cli.min_recommended_score),
+    ] {
+        let Some(min) = min else { continue };
+        if let Some(shortfall) = level.shortfall(min) {
+            report.diagnostic(&format!(
+                "{} score {:.2}% is below the minimum of {:.2}% by {:.2}%",
+                label, level.percentile, min, shortfall
+            ));
+            below_threshold = true;
+        }
+    }
+
+    report.suite_end(&stats);
+
+    let exit = Exit::from_stats(crashed, &stats, below_threshold);
+    Ok(ProgramRun { verdicts, exit })
+}
+
+fn run_diff(args: DiffArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let cases = suite::load(sources)?;
+    let env = parse_env(&args.runner_env)?;
+
+    let mut report = open_reporters(&args.reporter, &args.output, &args.program)?;
+    let mut program = Runner::new(&args.program);
+    let mut reference = Runner::new(&args.reference);
+    let mut stats = Stats::new(cases.len() as u32);
+
+    report.suite_start(&cases);
+    report.diagnostic(&format!(
+        "conform - comparing {} against {}",
+        args.program, args.reference
+    ));
+
+    // a program that doesn't start fails the first case to run, as in a run
+    let mut spawn_error = None;
+    for runner in [&mut program, &mut reference] {
+        runner.set_env_all(env.clone());
+        runner
+            .set_stderr(String::from("ignore"))
+            .context("failed to set stderr for the runner")?;
+        runner.set_timeout(args.timeout.map(Duration::from_secs_f64));
+        if let Err(err) = runner.spawn() {
+            let reason = format!("{:#}", err);
+            report.diagnostic(&reason);
+            spawn_error = Some(reason);
             break;
         }
+    }
 
-        let assertion = assert::case::assert(&case, &case_response.unwrap());
+    let mut crashed = spawn_error.is_some();
+    let mut disagreements = 0;
 
-        if !assertion.passed() {
-            tap.not_ok(num, &case.name);
-            tap.diagnostic(format!("{}", case));
-            tap.diagnostic(format!("{}", assertion));
+    for (pos, case) in cases.iter().enumerate() {
+        let number = pos as u32;
+        report.case_start(number, case);
 
-            stats.failed += 1;
-            if cli.exit_early {
-                break;
+        // only the program is timed, the reference sets no pace
+        let mut duration = None;
+        let outcome = if !harness::matches_filter(&args.filter, &case.name) {
+            Outcome::not_run()
+        } else if let Some(reason) = spawn_error.take() {
+            Outcome {
+                diagnostics: vec![reason],
+                ..Outcome::default()
             }
-
-            if case.is_recommended() && !cli.enforce_recommended {
-                stats.failed -= 1;
+        } else {
+            let start = Instant::now();
+            let left = program.send_case(case);
+            let elapsed = start.elapsed();
+            stats.record_duration(&case.name, elapsed);
+            duration = Some(elapsed);
+            match (left, reference.send_case(case)) {
+                (Ok(left), Ok(right)) => {
+                    match diff::compare(&case.payload, &left, &right, args.context) {
+                        Ok(None) => Outcome {
+                            case: Case::Passed,
+                            ..Outcome::default()
+                        },
+                        Ok(Some(disagreement)) => Outcome {
+                            diagnostics: vec![disagreement.diff],
+                            differences: disagreement.differences,
+                            ..Outcome::default()
+                        },
+                        Err(err) => Outcome::from_error(&err),
+                    }
+                }
+                (left, right) => {
+                    crashed = true;
+                    Outcome {
+                        diagnostics: [left.err(), right.err()]
+                            .into_iter()
+                            .flatten()
+                            .map(|err| format!("{:#}", err))
+                            .collect(),
+                        ..Outcome::default()
+                    }
+                }
             }
+        };
+
+        let verdict = if outcome.skipped() {
+            Verdict::Skipped
+        } else if outcome.passed() {
+            Verdict::Passed
         } else {
-            stats.passed += 1;
-            tap.ok(num, &case.name)
+            if !crashed {
+                disagreements += 1;
+            }
+            Verdict::Failed
+        };
+        report.case_finished(&CaseResult {
+            number,
+            case,
+            outcome: &outcome,
+            verdict,
+            duration,
+        });
+        stats.record(&case.level, verdict);
+
+        if crashed && spawn_error.is_none() {
+            break;
         }
     }
 
-    runner.kill().expect("failed to kill the runner");
+    for (runner, path) in [
+        (&mut program, &args.program),
+        (&mut reference, &args.reference),
+    ] {
+        if let Err(err) = runner.kill() {
+            report.diagnostic(&format!("failed to stop {}: {}", path, err));
+        }
+    }
 
     stats.calculate();
+    report.diagnostic(&format!("{} cases disagree", disagreements));
+    report.suite_end(&stats);
+    report.finish()?;
 
-    if stats.failed() {
-        tap.diagnostic(format!("test suite has failed ({}%)", stats.percentile));
+    let exit = if crashed {
+        Exit::TesteeCrashed
+    } else if disagreements > 0 {
+        Exit::Failures
     } else {
-        tap.diagnostic(format!("test suite has succeded ({}%)", stats.percentile));
+        Exit::Success
     };
+    Ok(exit)
+}
 
-    tap.diagnostic(format!("{}", stats));
+fn run_replay(args: ReplayArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let frames = recording::read(&args.recording)?;
+    let failure_list = match &args.failure_list {
+        Some(path) => FailureList::load(path)?,
+        None => FailureList::default(),
+    };
+    let suite = suite::load(sources)?;
+
+    // the recorded request is what the response answers, even if the suite
+    // has changed since. frames of cases the suite no longer has fail.
+    let mut replayed = Vec::new();
+    for frame in &frames {
+        let known = suite.iter().find(|c| c.name == frame.case);
+        let payload = frame.request()?;
+        let case = match known {
+            Some(case) => TestCase {
+                payload,
+                ..case.clone()
+            },
+            None => {
+                let request = ConformanceRequest::parse_from_bytes(&payload)
+                    .context("failed to parse the recorded request")?;
+                TestCase {
+                    name: frame.case.clone(),
+                    level: Level::Required,
+                    syntax: TestMessage::syntax_of(&request.message_type)
+                        .unwrap_or_default()
+                        .to_string(),
+                    payload,
+                    assert_by: AssertBy::Validator(None),
+                }
+            }
+        };
+        replayed.push((case, frame, known.is_some()));
+    }
+    let cases: Vec<TestCase> = replayed.iter().map(|(case, ..)| case.clone()).collect();
+
+    let mut report = open_reporters(&args.reporter, &args.output, &args.recording)?;
+    let mut stats = Stats::new(frames.len() as u32);
+
+    report.suite_start(&cases);
+    report.diagnostic(&format!("conform - replaying {}", args.recording));
+
+    let mut crashed = false;
+
+    for (pos, (case, frame, known)) in replayed.iter().enumerate() {
+        let number = pos as u32;
+        report.case_start(number, case);
+        stats.record_duration(&case.name, frame.duration());
+
+        let (outcome, verdict) = if !known {
+            let outcome = Outcome {
+                diagnostics: vec![format!("{} is not part of the suite", case.name)],
+                ..Outcome::default()
+            };
+            (outcome, Verdict::Failed)
+        } else {
+            match frame.response() {
+                Ok(response) => {
+                    let outcome = assert::case::assert(case, &response);
+                    let verdict = harness::judge(
+                        case,
+                        &outcome,
+                        failure_list.contains(&case.name),
+                        args.enforce_recommended,
+                    );
+                    (outcome, verdict)
+                }
+                Err(err) => {
+                    crashed = true;
+                    (Outcome::from_error(&err), Verdict::Failed)
+                }
+            }
+        };
+        report.case_finished(&CaseResult {
+            number,
+            case,
+            outcome: &outcome,
+            verdict,
+            duration: Some(frame.duration()),
+        });
+        if *known {
+            stats.record(&case.level, verdict);
+        } else {
+            // the level of the case is unknown, so it only fails the run
+            stats.failed += 1;
+        }
+    }
+
+    stats.calculate();
+    report.suite_end(&stats);
+    report.finish()?;
+
+    Ok(Exit::from_stats(crashed, &stats, false))
+}
+
+fn run_repro(args: ReproArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let case = find_case(suite::load(sources)?, &args.case)?;
+    let dir = args.output.unwrap_or(case.name.clone());
+    repro::export(&case, Path::new(&dir))?;
+    println!("wrote the reproducer of {} to {}", case.name, dir);
+    println!("run it with {}/run.sh <program>", dir);
+    Ok(Exit::Success)
+}
+
+fn run_one(mut args: RunOneArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let case = find_case(suite::load(sources)?, &args.case)?;
+    let request = case.request()?;
+
+    let mut runner = Runner::new(&args.program);
+    args.args.append(&mut args.trailing_args);
+    runner.set_args(&args.args);
+    if let Some(cwd) = &args.program_cwd {
+        runner.set_cwd(cwd);
+    }
+    runner.set_env_all(parse_env(&args.runner_env)?);
+    runner
+        .set_stderr(args.runner_stderr)
+        .context("failed to set stderr for the runner")?;
+    runner.set_timeout(args.timeout.map(Duration::from_secs_f64));
+
+    print_case(&case, &request)?;
+
+    if let Err(err) = runner.spawn() {
+        println!("{:#}", err);
+        return Ok(Exit::TesteeCrashed);
+    }
+
+    if args.pause {
+        match runner.pid() {
+            Some(pid) => println!(
+                "{} is running as pid {}, press enter to send the case",
+                args.program, pid
+            ),
+            None => println!(
+                "connected to {}, press enter to send the case",
+                args.program
+            ),
+        }
+        io::stdin()
+            .lock()
+            .read_line(&mut String::new())
+            .context("failed to read from stdin")?;
+    }
+
+    let start = Instant::now();
+    let raw = runner.send_case(&case);
+    let elapsed = start.elapsed();
+    if let Err(err) = runner.kill() {
+        eprintln!("failed to stop {}: {}", args.program, err);
+    }
+
+    let raw = match raw {
+        Ok(raw) => raw,
+        Err(err) => {
+            println!("== response");
+            println!("{:#}", err);
+            return Ok(Exit::TesteeCrashed);
+        }
+    };
+    let response =
+        ConformanceResponse::parse_from_bytes(&raw).context("failed to parse response")?;
+
+    println!("== response ({:.2}ms)", elapsed.as_secs_f64() * 1000.0);
+    println!("{}", inspect::describe_response(&request, &response));
+
+    match inspect::diff(&case, &response, args.context) {
+        Ok(Some(diff)) => {
+            println!("== diff (- response, + expected)");
+            println!("{}", diff);
+        }
+        Ok(None) => {}
+        Err(err) => println!("== diff\nfailed to diff: {:#}\n", err),
+    }
+
+    println!("== request frame");
+    let mut frame = (case.payload.len() as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(&case.payload);
+    println!("{}", inspect::hexdump(&frame));
+    println!("== response frame");
+    let mut frame = (raw.len() as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(&raw);
+    println!("{}", inspect::hexdump(&frame));
+
+    let assertion = assert::case::assert(&case, &raw);
+    let (outcome, exit) = if assertion.passed() {
+        ("passed", Exit::Success)
+    } else if assertion.skipped() {
+        ("skipped", Exit::Success)
+    } else {
+        ("failed", Exit::Failures)
+    };
+    println!("== outcome");
+    println!("{}", outcome);
+    for difference in &assertion.differences {
+        println!("{}", difference);
+    }
+    Ok(exit)
+}
+
+fn run_list(args: ListArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let cases = suite::load(sources)?;
+    let listings: Vec<Listing> = cases
+        .iter()
+        .filter(|case| harness::matches_filter(&args.filter, &case.name))
+        .map(|case| Listing {
+            name: &case.name,
+            level: case.level.to_string(),
+            syntax: &case.syntax,
+            assertion: case.assert_by.kind(),
+        })
+        .collect();
+
+    if args.json {
+        let json =
+            serde_json::to_string_pretty(&listings).context("failed to serialize the cases")?;
+        println!("{}", json);
+        return Ok(Exit::Success);
+    }
+
+    let width = listings.iter().map(|l| l.name.len()).max().unwrap_or(0);
+    for l in &listings {
+        println!(
+            "{:<width$}  {:<11}  {:<6}  {}",
+            l.name,
+            l.level,
+            l.syntax,
+            l.assertion,
+            width = width
+        );
+    }
+    Ok(Exit::Success)
+}
+
+fn run_show(args: ShowArgs, sources: &Sources) -> anyhow::Result<Exit> {
+    let case = find_case(suite::load(sources)?, &args.case)?;
+    let request = case.request()?;
+
+    print_case(&case, &request)?;
+    Ok(Exit::Success)
+}
+
+fn run_export_suite(args: ExportSuiteArgs) -> anyhow::Result<Exit> {
+    let cases = suite::extract()?;
+    suite::write(&args.file, &cases)?;
+    println!("wrote {} cases to {}", cases.len(), args.file);
+    Ok(Exit::Success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use conform::report::tap::Tap;
+
+    fn merged(args: &[&str], config: &str) -> Conform {
+        let mut cli = parse_args(["conform"].iter().chain(args)).unwrap();
+        cli.merge(toml::from_str(config).unwrap());
+        cli
+    }
+
+    fn program(spec: &str) -> (String, String) {
+        let program = Program::from(&spec.to_string());
+        (program.name, program.path)
+    }
+
+    #[test]
+    fn program_specs() {
+        assert_eq!(
+            program("./bin/runner"),
+            ("runner".into(), "./bin/runner".into())
+        );
+        assert_eq!(
+            program("ts=./runner.js"),
+            ("ts".into(), "./runner.js".into())
+        );
+        assert_eq!(
+            program("protoc-gen_ts=/a=b/runner"),
+            ("protoc-gen_ts".into(), "/a=b/runner".into())
+        );
+        assert_eq!(
+            program("tcp:localhost:9000"),
+            ("tcp:localhost:9000".into(), "tcp:localhost:9000".into())
+        );
+    }
+
+    #[test]
+    fn paths_with_equals_signs() {
+        assert_eq!(
+            program("./out/k=v/runner"),
+            ("runner".into(), "./out/k=v/runner".into())
+        );
+        assert_eq!(program("/opt/k=v"), ("k=v".into(), "/opt/k=v".into()));
+        assert_eq!(program("=runner"), ("=runner".into(), "=runner".into()));
+        assert_eq!(
+            program("1st=runner"),
+            ("1st=runner".into(), "1st=runner".into())
+        );
+    }
+
+    #[test]
+    fn global_flags_go_before_or_after_the_subcommand() {
+        for args in [
+            ["conform", "--suite", "s.json", "list"],
+            ["conform", "list", "--suite", "s.json"],
+        ] {
+            let cli = parse_args(args).unwrap();
+            assert_eq!(cli.suite.as_deref(), Some("s.json"));
+            assert!(matches!(cli.command, Some(Command::List(_))));
+        }
+    }
+
+    #[test]
+    fn run_flags_conflict_with_subcommands() {
+        let err = parse_args(["conform", "--program", "./runner", "list"])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        assert!(err
+            .to_string()
+            .contains("--program can't be used with the list command"));
+        assert!(parse_args(["conform", "--exit-early", "show", "Required.A"]).is_err());
+    }
+
+    #[test]
+    fn config_fills_in_missing_flags() {
+        let cli = merged(
+            &[],
+            indoc::indoc! {r#"
+                program = "./runner"
+                timeout = 5
+                exit_early = true
+                filter = ["Proto3"]
+            "#},
+        );
+        assert_eq!(cli.program, ["./runner"]);
+        assert_eq!(cli.timeout, Some(5.0));
+        assert!(cli.exit_early);
+        assert!(!cli.parallel);
+        assert_eq!(cli.filter, ["Proto3"]);
+    }
+
+    #[test]
+    fn flags_win_over_config() {
+        let cli = merged(
+            &[
+                "--program",
+                "./other",
+                "--timeout",
+                "1",
+                "--filter",
+                "Proto2",
+            ],
+            indoc::indoc! {r#"
+                program = "./runner"
+                timeout = 5
+                filter = ["Proto3"]
+            "#},
+        );
+        assert_eq!(cli.program, ["./other"]);
+        assert_eq!(cli.timeout, Some(1.0));
+        assert_eq!(cli.filter, ["Proto2"]);
+    }
+
+    #[test]
+    fn switches_turn_off_from_the_command_line() {
+        let config = indoc::indoc! {"
+            parallel = true
+            exit_early = true
+            enforce_recommended = true
+        "};
+        let cli = merged(&[], config);
+        assert!(cli.parallel && cli.exit_early && cli.enforce_recommended);
+
+        let cli = merged(
+            &[
+                "--no-parallel",
+                "--no-exit-early",
+                "--no-enforce-recommended",
+            ],
+            config,
+        );
+        assert!(!cli.parallel);
+        assert!(!cli.exit_early);
+        assert!(!cli.enforce_recommended);
+    }
+
+    #[test]
+    fn last_switch_wins() {
+        let cli = merged(&["--no-exit-early", "--exit-early"], "exit_early = false");
+        assert!(cli.exit_early);
+        let cli = merged(&["--exit-early", "--no-exit-early"], "exit_early = true");
+        assert!(!cli.exit_early);
+    }
+
+    #[test]
+    fn env_from_the_command_line_comes_last() {
+        let cli = merged(
+            &["--runner-env", "A=cli"],
+            indoc::indoc! {r#"
+                [env]
+                A = "config"
+                B = "config"
+            "#},
+        );
+        assert_eq!(cli.runner_env, ["A=config", "B=config", "A=cli"]);
+    }
+
+    #[test]
+    fn a_program_that_does_not_start_fails_the_run() {
+        let cli = merged(&["--program", "/nonexistent/testee"], "");
+        let cases = [TestCase {
+            name: "Required.Proto3.ProtobufInput.Case".to_string(),
+            level: Level::Required,
+            syntax: "proto3".to_string(),
+            payload: Vec::new(),
+            assert_by: AssertBy::ParseError,
+        }];
+        let harness = Harness::new(&cases);
+        let mut tap = Tap::new(Vec::new());
+        let program = Program::from(&cli.program[0]);
+        let run = run_program(&cli, &program, &cases, &harness, &mut tap).unwrap();
+
+        assert!(run.exit == Exit::TesteeCrashed);
+        let report = String::from_utf8(tap.into_inner()).unwrap();
+        assert!(report.contains("not ok 0 - Required.Proto3.ProtobufInput.Case"));
+        assert!(report.contains("# failed to spawn the testee program: "));
+        assert!(report.contains("# test suite has failed (0%)"));
+    }
+
+    #[test]
+    fn env_values_keep_their_equals_signs() {
+        let env = parse_env(&["A=b=c".to_string(), "B=".to_string()]).unwrap();
+        assert_eq!(
+            env,
+            [
+                ("A".to_string(), "b=c".to_string()),
+                ("B".to_string(), String::new())
+            ]
+        );
+        for invalid in ["FOO", "=x"] {
+            let err = parse_env(&[invalid.to_string()]).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("invalid --runner-env {}, expected KEY=VALUE", invalid)
+            );
+        }
+    }
 
-    if let Some(path) = cli.json_stats {
-        let json = stats.json().expect("failed to serialize stats into json");
-        fs::write(path, json).expect("failed to write json stats");
+    #[test]
+    fn one_reporter_writes_to_stdout() {
+        let specs: Vec<Spec> = ["tap", "junit", "terminal=out.txt"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let err = reporter_specs(&specs, "-", false).unwrap_err();
+        assert_eq!(err.to_string(), "only one reporter can write to stdout");
+        // several programs hold stdout back until their run is over
+        assert_eq!(reporter_specs(&specs, "-", true).unwrap(), specs);
+        assert!(reporter_specs(&specs[1..], "-", false).is_ok());
     }
 }
@@ -0,0 +1,46 @@
+use crate::stats::Verdict;
+use crate::test_case::TestCase;
+
+/// Outcome of every case per implementation, for certifying several
+/// implementations against the same suite.
+pub struct Matrix<'a> {
+    cases: &'a [TestCase],
+    columns: Vec<(&'a str, &'a [Option<Verdict>])>,
+}
+
+impl<'a> Matrix<'a> {
+    pub fn new(cases: &'a [TestCase]) -> Self {
+        Self {
+            cases,
+            columns: Vec::new(),
+        }
+    }
+
+    /// Adds the verdicts of an implementation, in the order of the cases. A
+    /// case without a verdict didn't run.
+    pub fn add(&mut self, name: &'a str, verdicts: &'a [Option<Verdict>]) {
+        self.columns.push((name, verdicts));
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("| case |");
+        for (name, _) in &self.columns {
+            out.push_str(&format!(" {} |", name));
+        }
+        out.push_str("\n|---|");
+        out.push_str(&"---|".repeat(self.columns.len()));
+        out.push('\n');
+
+        for (i, case) in self.cases.iter().enumerate() {
+            out.push_str(&format!("| {} |", case.name));
+            for (_, verdicts) in &self.columns {
+                match verdicts.get(i).copied().flatten() {
+                    Some(verdict) => out.push_str(&format!(" {} |", verdict)),
+                    None => out.push_str(" - |"),
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::time::Duration;
+
+use crate::test_case::TestCase;
+
+/// A single request/response exchange with the testee. Frames are stored as
+/// json lines, with the frames base64 encoded.
+#[derive(Serialize, Deserialize)]
+pub struct Frame {
+    pub case: String,
+    pub request: String,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub duration_us: u64,
+}
+
+impl Frame {
+    pub fn new(case: &TestCase, response: &anyhow::Result<Vec<u8>>, duration: Duration) -> Self {
+        let (response, error) = match response {
+            Ok(r) => (Some(STANDARD.encode(r)), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        Self {
+            case: case.name.clone(),
+            request: STANDARD.encode(&case.payload),
+            response,
+            error,
+            duration_us: duration.as_micros() as u64,
+        }
+    }
+
+    pub fn request(&self) -> anyhow::Result<Vec<u8>> {
+        STANDARD
+            .decode(&self.request)
+            .context("failed to decode the recorded request")
+    }
+
+    /// The recorded response, or the error the exchange ran into.
+    pub fn response(&self) -> anyhow::Result<Vec<u8>> {
+        match (&self.response, &self.error) {
+            (Some(r), _) => STANDARD
+                .decode(r)
+                .context("failed to decode the recorded response"),
+            (None, Some(e)) => Err(anyhow!("{}", e)),
+            (None, None) => Err(anyhow!("no response was recorded")),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(self.duration_us)
+    }
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create the recording {}", path))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.out, frame).context("failed to serialize frame")?;
+        writeln!(self.out).context("failed to write frame")?;
+        self.out.flush().context("failed to write frame")
+    }
+}
+
+pub fn read(path: &str) -> anyhow::Result<Vec<Frame>> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open the recording {}", path))?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .enumerate()
+        .map(|(i, line)| {
+            let line = line.context("failed to read the recording")?;
+            serde_json::from_str(&line)
+                .with_context(|| format!("failed to parse frame on line {}", i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_case::{AssertBy, Level};
+
+    #[test]
+    fn frames_survive_a_round_trip() {
+        let case = TestCase {
+            name: String::from("Required.Proto3.ProtobufInput.ValidDataScalar.INT32"),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: vec![1, 2, 3],
+            assert_by: AssertBy::ParseError,
+        };
+        let path = std::env::temp_dir().join(format!("conform-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut recorder = Recorder::create(path).unwrap();
+        recorder
+            .record(&Frame::new(
+                &case,
+                &Ok(vec![4, 5]),
+                Duration::from_micros(7),
+            ))
+            .unwrap();
+        let error = Err(anyhow!("testee closed").context("failed to read response"));
+        recorder
+            .record(&Frame::new(&case, &error, Duration::from_micros(9)))
+            .unwrap();
+        drop(recorder);
+
+        let frames = read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(frames.len(), 2);
+        for frame in &frames {
+            assert_eq!(frame.case, case.name);
+            assert_eq!(frame.request().unwrap(), case.payload);
+        }
+        assert_eq!(frames[0].response().unwrap(), [4, 5]);
+        assert_eq!(frames[0].duration(), Duration::from_micros(7));
+        let err = frames[1].response().unwrap_err();
+        assert_eq!(err.to_string(), "failed to read response: testee closed");
+        assert_eq!(frames[1].duration(), Duration::from_micros(9));
+    }
+}
@@ -0,0 +1,281 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, IsTerminal, Stdout, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use crate::report::junit::Junit;
+use crate::report::tap::Tap;
+use crate::report::terminal::{self, Terminal};
+use crate::report::{CaseResult, Report};
+use crate::stats::Stats;
+use crate::test_case::TestCase;
+
+/// Stdout of a reporter, held back until the run is over.
+type Held = Rc<RefCell<Vec<u8>>>;
+
+/// Where a reporter writes to.
+pub enum Output {
+    Stdout(Stdout),
+    File(File),
+    Buffer(Held),
+}
+
+impl Output {
+    /// `-` is stdout, anything else a file.
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        if path == "-" {
+            return Ok(Self::Stdout(io::stdout()));
+        }
+        let file = File::create(path)
+            .with_context(|| format!("failed to open the report file {}", path))?;
+        Ok(Self::File(file))
+    }
+
+    /// Whether the output goes to a terminal, held back stdout never does.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Self::Stdout(out) => out.is_terminal(),
+            Self::File(file) => file.is_terminal(),
+            Self::Buffer(_) => false,
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(out) => out.write(buf),
+            Self::File(file) => file.write(buf),
+            Self::Buffer(held) => held.borrow_mut().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(out) => out.flush(),
+            Self::File(file) => file.flush(),
+            Self::Buffer(_) => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Tap,
+    Junit,
+    Terminal,
+}
+
+/// A reporter to attach to a run, written as `kind[=path]` such as
+/// `junit=report.xml`. The path defaults to stdout.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "String")]
+pub struct Spec {
+    pub kind: Kind,
+    pub path: String,
+}
+
+impl FromStr for Spec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (kind, path) = s.split_once('=').unwrap_or((s, "-"));
+        let kind = match kind {
+            "tap" => Kind::Tap,
+            "junit" => Kind::Junit,
+            "terminal" => Kind::Terminal,
+            other => {
+                return Err(anyhow!(
+                    "unknown reporter {}, expected tap, junit or terminal",
+                    other
+                ))
+            }
+        };
+        Ok(Self {
+            kind,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Spec {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+/// Sends everything reported to several reporters at once.
+#[derive(Default)]
+pub struct Fanout {
+    reporters: Vec<Box<dyn Report>>,
+    /// Stdout of the reporters opened as buffered, in the order they were
+    /// opened.
+    held: Vec<Held>,
+}
+
+impl Fanout {
+    pub fn add(&mut self, reporter: Box<dyn Report>) {
+        self.reporters.push(reporter);
+    }
+
+    /// Opens the reporter described by `spec`, writing to `path`. `name` is
+    /// the name of the program under test. When `buffered`, stdout is only
+    /// written by [`Report::finish`], so reports of programs running in
+    /// parallel don't interleave.
+    pub fn open(
+        &mut self,
+        spec: &Spec,
+        path: &str,
+        name: &str,
+        buffered: bool,
+    ) -> anyhow::Result<()> {
+        let output = if path == "-" && buffered {
+            let held = Held::default();
+            self.held.push(Rc::clone(&held));
+            Output::Buffer(held)
+        } else {
+            Output::open(path)?
+        };
+        self.add(match spec.kind {
+            Kind::Tap => Box::new(Tap::new(output)),
+            Kind::Junit => Box::new(Junit::new(output, name)),
+            Kind::Terminal => {
+                let interactive = output.is_terminal();
+                let color = interactive && terminal::color_enabled();
+                Box::new(Terminal::new(output, color, interactive))
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Report for Fanout {
+    fn suite_start(&mut self, cases: &[TestCase]) {
+        for r in &mut self.reporters {
+            r.suite_start(cases);
+        }
+    }
+
+    fn case_start(&mut self, number: u32, case: &TestCase) {
+        for r in &mut self.reporters {
+            r.case_start(number, case);
+        }
+    }
+
+    fn case_finished(&mut self, result: &CaseResult) {
+        for r in &mut self.reporters {
+            r.case_finished(result);
+        }
+    }
+
+    fn diagnostic(&mut self, message: &str) {
+        for r in &mut self.reporters {
+            r.diagnostic(message);
+        }
+    }
+
+    fn suite_end(&mut self, stats: &Stats) {
+        for r in &mut self.reporters {
+            r.suite_end(stats);
+        }
+    }
+
+    /// Finishes every reporter and writes out whatever was held back for
+    /// stdout, returning the first error.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for r in &mut self.reporters {
+            let finished = r.finish();
+            if result.is_ok() {
+                result = finished;
+            }
+        }
+        let mut stdout = io::stdout().lock();
+        for held in &self.held {
+            stdout
+                .write_all(&held.borrow())
+                .context("failed to write the report")?;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::case::Outcome;
+    use crate::stats::Verdict;
+    use crate::test_case::{AssertBy, Level};
+
+    #[test]
+    fn specs() {
+        let spec: Spec = "junit=report.xml".parse().unwrap();
+        assert_eq!(spec.kind, Kind::Junit);
+        assert_eq!(spec.path, "report.xml");
+        let spec: Spec = "tap".parse().unwrap();
+        assert_eq!((spec.kind, spec.path.as_str()), (Kind::Tap, "-"));
+        let spec: Spec = "terminal=a=b".parse().unwrap();
+        assert_eq!((spec.kind, spec.path.as_str()), (Kind::Terminal, "a=b"));
+        let err = "html=report.html".parse::<Spec>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown reporter html, expected tap, junit or terminal"
+        );
+    }
+
+    #[test]
+    fn every_reporter_sees_every_event() {
+        let tap = Held::default();
+        let junit = Held::default();
+        let mut fanout = Fanout::default();
+        fanout.add(Box::new(Tap::new(Output::Buffer(Rc::clone(&tap)))));
+        fanout.add(Box::new(Junit::new(
+            Output::Buffer(Rc::clone(&junit)),
+            "runner",
+        )));
+
+        let cases = [TestCase {
+            name: String::from("Required.Proto3.ProtobufInput.Case"),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: Vec::new(),
+            assert_by: AssertBy::ParseError,
+        }];
+        fanout.suite_start(&cases);
+        fanout.case_start(0, &cases[0]);
+        fanout.case_finished(&CaseResult {
+            number: 0,
+            case: &cases[0],
+            outcome: &Outcome::default(),
+            verdict: Verdict::Failed,
+            duration: None,
+        });
+        fanout.diagnostic("spawned");
+        let mut stats = Stats::new(1);
+        stats.record(&Level::Required, Verdict::Failed);
+        stats.calculate();
+        fanout.suite_end(&stats);
+        fanout.finish().unwrap();
+
+        let tap = String::from_utf8(tap.borrow().clone()).unwrap();
+        assert!(
+            tap.contains("not ok 0 - Required.Proto3.ProtobufInput.Case"),
+            "{}",
+            tap
+        );
+        assert!(tap.contains("# spawned"), "{}", tap);
+        let junit = String::from_utf8(junit.borrow().clone()).unwrap();
+        assert!(junit.contains(r#"tests="1" failures="1""#), "{}", junit);
+        assert!(
+            junit.contains("<system-out>spawned</system-out>"),
+            "{}",
+            junit
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::Write;
+
+use anyhow::Context;
+
+use crate::report::{CaseResult, Report};
+use crate::stats::{Stats, Verdict};
+use crate::test_case::TestCase;
+
+enum Status {
+    Passed,
+    Failed,
+    /// Skipped, or not counted as a failure, with the reason.
+    Skipped(String),
+}
+
+struct Case {
+    name: String,
+    status: Status,
+    /// Seconds the program took to answer.
+    time: f64,
+    output: Vec<String>,
+}
+
+/// JUnit XML report, as understood by most CI systems. Cases are collected
+/// and the document is written when the suite ends.
+pub struct Junit<T: Write> {
+    stream: T,
+    name: String,
+    cases: Vec<Case>,
+    output: Vec<String>,
+    /// Error writing the document, returned by [`Report::finish`].
+    error: Option<std::io::Error>,
+}
+
+impl<T> Junit<T>
+where
+    T: Write,
+{
+    /// `name` is the name of the test suite, usually the program under test.
+    pub fn new(stream: T, name: &str) -> Self {
+        Self {
+            stream,
+            name: name.to_string(),
+            cases: Vec::new(),
+            output: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    fn write(&mut self, stats: &Stats) -> std::io::Result<()> {
+        let failures = self
+            .cases
+            .iter()
+            .filter(|c| matches!(c.status, Status::Failed))
+            .count();
+        let skipped = self
+            .cases
+            .iter()
+            .filter(|c| matches!(c.status, Status::Skipped(_)))
+            .count();
+
+        let s = &mut self.stream;
+        writeln!(s, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(s, "<testsuites>")?;
+        writeln!(
+            s,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.3}">"#,
+            escape(&self.name),
+            self.cases.len(),
+            failures,
+            skipped,
+            stats.timing.total / 1000.0
+        )?;
+        for case in &self.cases {
+            write!(
+                s,
+                r#"    <testcase name="{}" classname="{}" time="{:.6}""#,
+                escape(&case.name),
+                escape(&self.name),
+                case.time
+            )?;
+            match &case.status {
+                Status::Passed => writeln!(s, "/>")?,
+                Status::Failed => {
+                    writeln!(s, ">")?;
+                    writeln!(
+                        s,
+                        r#"      <failure message="failed">{}</failure>"#,
+                        escape(&case.output.join("\n"))
+                    )?;
+                    writeln!(s, "    </testcase>")?;
+                }
+                Status::Skipped(reason) => {
+                    writeln!(s, ">")?;
+                    writeln!(s, r#"      <skipped message="{}"/>"#, escape(reason))?;
+                    writeln!(s, "    </testcase>")?;
+                }
+            }
+        }
+        if !self.output.is_empty() {
+            writeln!(
+                s,
+                "    <system-out>{}</system-out>",
+                escape(&self.output.join("\n"))
+            )?;
+        }
+        writeln!(s, "  </testsuite>")?;
+        writeln!(s, "</testsuites>")?;
+        s.flush()
+    }
+}
+
+impl<T> Report for Junit<T>
+where
+    T: Write,
+{
+    fn suite_start(&mut self, _cases: &[TestCase]) {}
+
+    fn case_finished(&mut self, result: &CaseResult) {
+        let status = match result.verdict {
+            Verdict::Passed | Verdict::UnexpectedPass => Status::Passed,
+            Verdict::Failed => Status::Failed,
+            // expected failures and recommended cases don't fail the suite
+            Verdict::Skipped => Status::Skipped("skipped".to_string()),
+            Verdict::ExpectedFailure => Status::Skipped("expected failure".to_string()),
+            Verdict::Warning => Status::Skipped("recommended".to_string()),
+        };
+        let output = match status {
+            Status::Failed => vec![format!("{}", result.case), format!("{}", result.outcome)],
+            _ => Vec::new(),
+        };
+        self.cases.push(Case {
+            name: result.case.name.clone(),
+            status,
+            time: result.duration.unwrap_or_default().as_secs_f64(),
+            output,
+        });
+    }
+
+    fn diagnostic(&mut self, message: &str) {
+        self.output.push(message.to_string());
+    }
+
+    fn suite_end(&mut self, stats: &Stats) {
+        self.error = self.write(stats).err();
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err).context("failed to write the junit report"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // characters that are not allowed in xml 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::case::Outcome;
+    use crate::test_case::{AssertBy, Level};
+
+    fn case(name: &str) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: Vec::new(),
+            assert_by: AssertBy::ParseError,
+        }
+    }
+
+    #[test]
+    fn counts_failures_and_skips() {
+        let cases = [case("a"), case("b"), case("c"), case("d"), case("e")];
+        let verdicts = [
+            Verdict::Passed,
+            Verdict::Failed,
+            Verdict::Skipped,
+            Verdict::ExpectedFailure,
+            Verdict::UnexpectedPass,
+        ];
+        let mut junit = Junit::new(Vec::new(), "runner <ts>");
+        junit.suite_start(&cases);
+        for (number, (case, verdict)) in cases.iter().zip(verdicts).enumerate() {
+            junit.case_finished(&CaseResult {
+                number: number as u32,
+                case,
+                outcome: &Outcome::default(),
+                verdict,
+                duration: None,
+            });
+        }
+        junit.suite_end(&Stats::new(5));
+        junit.finish().unwrap();
+
+        let xml = String::from_utf8(junit.into_inner()).unwrap();
+        assert!(
+            xml.contains(
+                r#"<testsuite name="runner &lt;ts&gt;" tests="5" failures="1" skipped="2""#
+            ),
+            "{}",
+            xml
+        );
+        assert!(xml.contains(r#"<skipped message="expected failure"/>"#));
+        assert_eq!(xml.matches("<failure ").count(), 1);
+    }
+
+    #[test]
+    fn escapes_markup_and_drops_control_characters() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>\u{1}\n"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;\n"
+        );
+    }
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_errors_come_out_of_finish() {
+        let mut junit = Junit::new(Closed, "runner");
+        junit.suite_start(&[]);
+        junit.suite_end(&Stats::new(0));
+        let err = junit.finish().unwrap_err();
+        assert!(err.to_string().contains("failed to write the junit report"));
+    }
+}
@@ -2,8 +2,13 @@ use auto_enums::auto_enum;
 use std::{
     fs::File,
     io::{stdout, Write},
+    time::Duration,
 };
 
+use crate::assert::case::Outcome;
+use crate::stats::{Stats, Verdict};
+use crate::test_case::TestCase;
+
 #[auto_enum(Write)]
 pub fn get_output(report_to: &String) -> impl Write {
     if report_to == "-" {
@@ -13,17 +18,37 @@ pub fn get_output(report_to: &String) -> impl Write {
     }
 }
 
+/// A case that has been judged, as given to [`Report::case_finished`].
+pub struct CaseResult<'a> {
+    /// Position of the case in the suite.
+    pub number: u32,
+    pub case: &'a TestCase,
+    pub outcome: &'a Outcome,
+    /// What the outcome amounts to, once the failure list and the
+    /// enforcement policy are applied.
+    pub verdict: Verdict,
+    /// How long the program took to answer, if the case was sent.
+    pub duration: Option<Duration>,
+}
+
+/// Receives the events of a run, in order: the suite starts, every case
+/// starts and finishes, and the suite ends. Diagnostics may come at any
+/// point in between.
 pub trait Report {
-    fn plan(&mut self, start: u32, finish: u32);
-    fn ok<S>(&mut self, number: u32, name: S)
-    where
-        S: Into<String>;
-    fn not_ok<S>(&mut self, number: u32, name: S)
-    where
-        S: Into<String>;
-    fn diagnostic<S>(&mut self, message: S)
-    where
-        S: Into<String>;
+    fn suite_start(&mut self, cases: &[TestCase]);
+    fn case_start(&mut self, _number: u32, _case: &TestCase) {}
+    fn case_finished(&mut self, result: &CaseResult);
+    /// Free form notes about the run, such as the program being spawned.
+    fn diagnostic(&mut self, message: &str);
+    fn suite_end(&mut self, stats: &Stats);
+    /// Called last, once the report is complete, with what went wrong
+    /// writing it out if anything did.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
+pub mod fanout;
+pub mod junit;
 pub mod tap;
+pub mod terminal;
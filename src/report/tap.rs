@@ -1,6 +1,8 @@
 use std::io::Write;
 
-use crate::report::Report;
+use crate::report::{CaseResult, Report};
+use crate::stats::{Stats, Verdict};
+use crate::test_case::TestCase;
 
 /// Global constant for the "ok"
 const OK_SYMBOL: &str = "ok";
@@ -18,25 +20,24 @@ where
     pub fn new(stream: T) -> Self {
         Self { stream }
     }
-}
 
-impl<T> Report for Tap<T>
-where
-    T: Write,
-{
-    fn plan(&mut self, start: u32, finish: u32) {
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    pub fn plan(&mut self, start: u32, finish: u32) {
         writeln!(self.stream, "TAP version 14").unwrap();
         writeln!(self.stream, "{}..{}", start, finish).unwrap();
     }
 
-    fn ok<S>(&mut self, number: u32, name: S)
+    pub fn ok<S>(&mut self, number: u32, name: S)
     where
         S: Into<String>,
     {
         writeln!(self.stream, "{} {} - {}", OK_SYMBOL, number, name.into()).unwrap();
     }
 
-    fn not_ok<S>(&mut self, number: u32, name: S)
+    pub fn not_ok<S>(&mut self, number: u32, name: S)
     where
         S: Into<String>,
     {
@@ -50,7 +51,7 @@ where
         .unwrap();
     }
 
-    fn diagnostic<S>(&mut self, message: S)
+    pub fn diagnostic<S>(&mut self, message: S)
     where
         S: Into<String>,
     {
@@ -62,3 +63,50 @@ where
         writeln!(self.stream, "# {}", m).unwrap();
     }
 }
+
+impl<T> Report for Tap<T>
+where
+    T: Write,
+{
+    fn suite_start(&mut self, cases: &[TestCase]) {
+        self.plan(0, cases.len() as u32);
+    }
+
+    fn case_finished(&mut self, result: &CaseResult) {
+        let (number, name) = (result.number, &result.case.name);
+        match result.verdict {
+            Verdict::Skipped => self.ok(number, format!("{} # SKIP", name)),
+            Verdict::UnexpectedPass => {
+                self.ok(number, name);
+                self.diagnostic("passed unexpectedly, remove it from the failure list");
+            }
+            Verdict::Passed => self.ok(number, name),
+            Verdict::ExpectedFailure => {
+                self.not_ok(number, format!("{} # TODO expected failure", name))
+            }
+            Verdict::Warning => {
+                self.not_ok(number, format!("{} # TODO recommended", name));
+                self.diagnostic(format!("{}", result.case));
+                self.diagnostic(format!("{}", result.outcome));
+            }
+            Verdict::Failed => {
+                self.not_ok(number, name);
+                self.diagnostic(format!("{}", result.case));
+                self.diagnostic(format!("{}", result.outcome));
+            }
+        }
+    }
+
+    fn diagnostic(&mut self, message: &str) {
+        Tap::diagnostic(self, message);
+    }
+
+    fn suite_end(&mut self, stats: &Stats) {
+        if stats.failed() {
+            self.diagnostic(format!("test suite has failed ({}%)", stats.percentile));
+        } else {
+            self.diagnostic(format!("test suite has succeeded ({}%)", stats.percentile));
+        };
+        self.diagnostic(format!("{}", stats));
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::{IsTerminal, Write};
+
+use crate::report::{CaseResult, Report};
+use crate::stats::{Stats, Verdict};
+use crate::test_case::TestCase;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Erases the line the cursor is on, where the progress bar is drawn.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+const BAR_WIDTH: usize = 30;
+
+/// Report for humans at a terminal: a live progress bar with the counts so
+/// far, failures printed in full with colored diffs and a final summary.
+pub struct Terminal<T: Write> {
+    stream: T,
+    color: bool,
+    /// Whether the stream is a terminal the progress bar can be redrawn on.
+    progress: bool,
+    total: u32,
+    passed: u32,
+    failed: u32,
+    skipped: u32,
+    todo: u32,
+    /// Name of the case the program is working on.
+    current: Option<String>,
+    ended: bool,
+}
+
+impl<T> Terminal<T>
+where
+    T: Write,
+{
+    pub fn new(stream: T, color: bool, progress: bool) -> Self {
+        Self {
+            stream,
+            color,
+            progress,
+            total: 0,
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            todo: 0,
+            current: None,
+            ended: false,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn done(&self) -> u32 {
+        self.passed + self.failed + self.skipped + self.todo
+    }
+
+    fn counts(&self) -> String {
+        [
+            (GREEN, self.passed, "passed"),
+            (RED, self.failed, "failed"),
+            (YELLOW, self.todo, "todo"),
+            (DIM, self.skipped, "skipped"),
+        ]
+        .iter()
+        .map(|(color, count, label)| self.paint(color, &format!("{} {}", count, label)))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    fn draw_progress(&mut self) {
+        if !self.progress {
+            return;
+        }
+        let done = self.done();
+        let filled = if self.total == 0 {
+            BAR_WIDTH
+        } else {
+            (done as usize * BAR_WIDTH / self.total as usize).min(BAR_WIDTH)
+        };
+        let bar = format!("{}{}", "=".repeat(filled), " ".repeat(BAR_WIDTH - filled));
+        let current = match &self.current {
+            Some(name) => format!(" {}", self.paint(DIM, name)),
+            None => String::new(),
+        };
+        write!(
+            self.stream,
+            "{}[{}] {}/{} {}{}",
+            CLEAR_LINE,
+            bar,
+            done,
+            self.total,
+            self.counts(),
+            current
+        )
+        .unwrap();
+        self.stream.flush().unwrap();
+    }
+
+    /// Writes a line above the progress bar.
+    fn line(&mut self, line: &str) {
+        let clear = if self.progress { CLEAR_LINE } else { "" };
+        writeln!(self.stream, "{}{}", clear, line).unwrap();
+    }
+
+    fn colorize(&self, line: &str) -> String {
+        if line.starts_with("@@") {
+            return self.paint(CYAN, line);
+        }
+        if line.starts_with('+') {
+            return self.paint(GREEN, line);
+        }
+        if line.starts_with('-') {
+            return self.paint(RED, line);
+        }
+        // a differing field, `path: expected X, got Y`
+        if let Some((path, rest)) = line.split_once(": expected ") {
+            if let Some((expected, actual)) = rest.rsplit_once(", got ") {
+                return format!(
+                    "{}: expected {}, got {}",
+                    self.paint(BOLD, path),
+                    self.paint(GREEN, expected),
+                    self.paint(RED, actual)
+                );
+            }
+        }
+        line.to_string()
+    }
+}
+
+impl<T> Report for Terminal<T>
+where
+    T: Write,
+{
+    fn suite_start(&mut self, cases: &[TestCase]) {
+        self.total = cases.len() as u32;
+        self.draw_progress();
+    }
+
+    fn case_start(&mut self, _number: u32, case: &TestCase) {
+        self.current = Some(case.name.clone());
+        self.draw_progress();
+    }
+
+    fn case_finished(&mut self, result: &CaseResult) {
+        self.current = None;
+        match result.verdict {
+            Verdict::Passed => self.passed += 1,
+            Verdict::UnexpectedPass => {
+                self.passed += 1;
+                let note = self.paint(
+                    YELLOW,
+                    &format!(
+                        "{}: passed unexpectedly, remove it from the failure list",
+                        result.case.name
+                    ),
+                );
+                self.line(&note);
+            }
+            Verdict::Skipped => self.skipped += 1,
+            Verdict::ExpectedFailure | Verdict::Warning => self.todo += 1,
+            Verdict::Failed => {
+                self.failed += 1;
+                let header = self.paint(RED, &format!("FAIL {}", result.case.name));
+                self.line(&header);
+                let details = format!("{}\n{}", result.case, result.outcome);
+                for line in details.lines() {
+                    let line = format!("    {}", self.colorize(line));
+                    self.line(&line);
+                }
+            }
+        }
+        self.draw_progress();
+    }
+
+    fn diagnostic(&mut self, message: &str) {
+        for line in message.lines() {
+            let line = self.paint(DIM, line);
+            self.line(&line);
+        }
+        if !self.ended {
+            self.draw_progress();
+        }
+    }
+
+    fn suite_end(&mut self, stats: &Stats) {
+        self.ended = true;
+        let summary = format!("{} of {} cases: {}", self.done(), self.total, self.counts());
+        self.line(&summary);
+        let verdict = if stats.failed() {
+            self.paint(
+                RED,
+                &format!("test suite has failed ({:.2}%)", stats.percentile),
+            )
+        } else {
+            self.paint(
+                GREEN,
+                &format!("test suite has succeeded ({:.2}%)", stats.percentile),
+            )
+        };
+        self.line(&verdict);
+    }
+}
+
+/// Whether stdout is a terminal, where [`Terminal`] is preferred over TAP.
+pub fn is_terminal() -> bool {
+    std::io::stdout().is_terminal()
+}
+
+/// Colors are on unless `NO_COLOR` is set to a non empty value, see
+/// <https://no-color.org>.
+pub fn color_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::case::Outcome;
+    use crate::test_case::{AssertBy, Level};
+
+    #[test]
+    fn plain_output_has_no_escape_sequences() {
+        let case = TestCase {
+            name: String::from("Required.Proto3.JsonInput.Int32"),
+            level: Level::Required,
+            syntax: String::from("proto3"),
+            payload: Vec::new(),
+            assert_by: AssertBy::ParseError,
+        };
+        let cases = [case];
+        let mut terminal = Terminal::new(Vec::new(), false, false);
+        terminal.suite_start(&cases);
+        terminal.case_start(0, &cases[0]);
+        terminal.case_finished(&CaseResult {
+            number: 0,
+            case: &cases[0],
+            outcome: &Outcome::default(),
+            verdict: Verdict::Failed,
+            duration: None,
+        });
+        terminal.diagnostic("1 failed");
+        let mut stats = Stats::new(1);
+        stats.record(&Level::Required, Verdict::Failed);
+        terminal.suite_end(&stats);
+
+        let out = String::from_utf8(terminal.into_inner()).unwrap();
+        assert!(!out.contains('\x1b') && !out.contains('\r'), "{:?}", out);
+        assert!(
+            out.starts_with("FAIL Required.Proto3.JsonInput.Int32\n"),
+            "{}",
+            out
+        );
+        assert!(out.ends_with("test suite has failed (0.00%)\n"), "{}", out);
+    }
+}
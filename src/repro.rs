@@ -0,0 +1,70 @@
+use anyhow::Context;
+use protobuf::text_format;
+use std::fs;
+use std::path::Path;
+
+use crate::inspect;
+use crate::test_case::TestCase;
+
+/// Writes everything needed to reproduce a case outside of conform into `dir`:
+///
+/// - `request.bin`, the binary `ConformanceRequest`
+/// - `request.txt`, the same request in text format
+/// - `expected.txt`, what the response is expected to be
+/// - `run.sh`, which pipes the length-prefixed request into a program
+pub fn export(case: &TestCase, dir: &Path) -> anyhow::Result<()> {
+    let request = case.request()?;
+
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    write(dir, "request.bin", &case.payload)?;
+    write(
+        dir,
+        "request.txt",
+        text_format::print_to_string_pretty(&request).as_bytes(),
+    )?;
+
+    let expected = inspect::expected(case)?;
+    write(dir, "expected.txt", expected.as_bytes())?;
+
+    let length: String = (case.payload.len() as u32)
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("\\{:03o}", b))
+        .collect();
+    let script = indoc::formatdoc! {r#"
+        #!/bin/sh
+        # Reproduces {name}
+        #
+        # Usage: ./run.sh <program> [args...] > response.bin
+        #
+        # The response is length-prefixed too, `tail -c +5 response.bin` gives
+        # the binary ConformanceResponse.
+        set -e
+        dir="$(cd "$(dirname "$0")" && pwd)"
+        {{ printf '{length}'; cat "$dir/request.bin"; }} | "$@"
+    "#,
+        name = case.name,
+        length = length,
+    };
+    write(dir, "run.sh", script.as_bytes())?;
+    make_executable(&dir.join("run.sh"))?;
+    Ok(())
+}
+
+fn write(dir: &Path, name: &str, contents: &[u8]) -> anyhow::Result<()> {
+    let path = dir.join(name);
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("failed to make {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
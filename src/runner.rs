@@ -1,95 +1,174 @@
 use anyhow::{anyhow, Context, Ok};
 use std::fs;
 use std::io::{self, Read, Write};
-use std::process::{Child, Command, Stdio};
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::recording::{Frame, Recorder};
 use crate::test_case::TestCase;
+use crate::transport::{self, Input, Transport};
 
 pub struct Runner {
-    command: Command,
-    child: Option<Child>,
+    transport: Box<dyn Transport>,
+    input: Option<Input>,
+    responses: Option<Receiver<anyhow::Result<Vec<u8>>>>,
+    timeout: Option<Duration>,
+    recorder: Option<Recorder>,
 }
 
 
 
 impl Runner {
-    pub fn new(program: &String) -> Self {
+    /// Runner for the testee at `program`, which is either a program or the
+    /// address of a socket, see [`transport::from_address`].
+    pub fn new(program: &str) -> Self {
+        Self::with_transport(transport::from_address(program))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
         Self {
-            command: Command::new(&program),
-            child: None,
+            transport,
+            input: None,
+            responses: None,
+            timeout: None,
+            recorder: None,
+        }
+    }
+
+    // the command settings only apply to a program that is spawned, a
+    // testee on a socket is already running.
+
+    pub fn set_args(&mut self, args: &Vec<String>) {
+        if let Some(command) = self.transport.command() {
+            command.args(args);
+        }
+    }
+
+    pub fn set_cwd(&mut self, cwd: &String) {
+        if let Some(command) = self.transport.command() {
+            command.current_dir(cwd);
         }
     }
-    
 
     pub fn set_env(&mut self, k: String, v: String) {
-        self.command.env(k, v);
+        if let Some(command) = self.transport.command() {
+            command.env(k, v);
+        }
     }
 
     pub fn set_env_all(&mut self, env: Vec<(String, String)>) {
-        for (k, v) in env.iter() {
-            self.command.env(k, v);
+        if let Some(command) = self.transport.command() {
+            for (k, v) in env.iter() {
+                command.env(k, v);
+            }
         }
     }
 
     pub fn set_stderr(&mut self, stderr: String) -> anyhow::Result<()> {
         let stderr = match stderr.as_str() {
             "" | "ignore" => Stdio::null(),
+            "inherit" => Stdio::inherit(),
             _ => {
                 let file = fs::File::create(stderr).with_context(|| "failed to create stderr file for the runner")?;
                 Stdio::from(file)
             },
         };
         
-        self.command.stderr(stderr);
+        if let Some(command) = self.transport.command() {
+            command.stderr(stderr);
+        }
+        Ok(())
+    }
+
+    /// Sets how long to wait for the response of a single case.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Records every exchange with the testee to `path`, for replaying later.
+    pub fn set_record(&mut self, path: &str) -> anyhow::Result<()> {
+        self.recorder = Some(Recorder::create(path)?);
         Ok(())
     }
 
+    /// Spawns or connects to the testee.
     pub fn spawn(&mut self) -> anyhow::Result<&mut Self> {
-        if self.child.is_some() {
-            return Err(anyhow!("program is already running"));
-        }
-    
-        let child = self
-            .command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .with_context(|| "failed to spawn the testee program")?;
+        let (input, mut output) = self.transport.open()?;
+
+        // responses are read on a separate thread so that a hanging testee
+        // can be abandoned once the timeout expires.
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            let response = read_response(&mut output);
+            let closed = response.is_err();
+            if tx.send(response).is_err() || closed {
+                break;
+            }
+        });
         
-        self.child = Some(child);
+        self.input = Some(input);
+        self.responses = Some(rx);
         Ok(self)
     }
 
     pub fn send_case(&mut self, case: &TestCase) -> anyhow::Result<Vec<u8>> {
-        if self.child.is_none() {
-            return Err(anyhow!("program is not running"));
+        let start = Instant::now();
+        let response = self.exchange(case);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&Frame::new(case, &response, start.elapsed()))?;
         }
-        let child = self.child.as_mut().unwrap();
-        let stdin = child.stdin.as_mut().unwrap();
-        let stdout = child.stdout.as_mut().unwrap();
+        response
+    }
+
+    fn exchange(&mut self, case: &TestCase) -> anyhow::Result<Vec<u8>> {
+        let (Some(input), Some(responses)) = (self.input.as_mut(), self.responses.as_ref()) else {
+            return Err(anyhow!("program is not running"));
+        };
 
         let plen = case.payload.len() as u32;
-        stdin
-            .write(&plen.to_le_bytes())
-            .with_context(|| "failed to write payload length to stdin")?;
-        stdin
-            .write(&case.payload)
-            .with_context(|| "failed to write payload to stdin")?;
-
-        let mut rlen = [0u8; 4];
-        stdout
-            .read_exact(&mut rlen)
-            .with_context(|| "failed to read response length from stdout")?;
-
-        let mut r = vec![0u8; u32::from_le_bytes(rlen) as usize];
-
-        stdout
-            .read_exact(&mut r)
-            .with_context(|| "failed to read response from stdout")?;
-        Ok(r)
+        input
+            .write_all(&plen.to_le_bytes())
+            .with_context(|| "failed to write payload length to the testee")?;
+        input
+            .write_all(&case.payload)
+            .with_context(|| "failed to write payload to the testee")?;
+        input.flush().with_context(|| "failed to send the payload to the testee")?;
+
+        let response = match self.timeout {
+            Some(timeout) => responses.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => {
+                    anyhow!("testee did not respond within {:?}", timeout)
+                }
+                RecvTimeoutError::Disconnected => anyhow!("testee closed its output"),
+            })?,
+            None => responses.recv().with_context(|| "testee closed its output")?,
+        };
+        response
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.transport.pid()
     }
 
+    /// Stops the testee, or disconnects from it.
     pub fn kill(&mut self) -> anyhow::Result<(), io::Error> {
-        self.child.as_mut().unwrap().kill()
+        self.input = None;
+        self.transport.close()
     }
 }
+
+fn read_response(output: &mut impl Read) -> anyhow::Result<Vec<u8>> {
+    let mut rlen = [0u8; 4];
+    output
+        .read_exact(&mut rlen)
+        .with_context(|| "failed to read response length from the testee")?;
+
+    let mut r = vec![0u8; u32::from_le_bytes(rlen) as usize];
+
+    output
+        .read_exact(&mut r)
+        .with_context(|| "failed to read response from the testee")?;
+    Ok(r)
+}
@@ -1,15 +1,111 @@
 use core::fmt;
+use std::cmp::Reverse;
 use std::fmt::Formatter;
+use std::time::Duration;
 
 use serde::Serialize;
 
+use crate::test_case::Level;
+
 #[derive(Serialize)]
 pub struct Stats {
     pub total: u32,
     pub passed: u32,
     pub skipped: u32,
     pub failed: u32,
+    /// Recommended cases that failed while they were not enforced.
+    pub warnings: u32,
+    pub expected_failures: u32,
+    pub unexpected_passes: u32,
     pub percentile: f32,
+    pub required: LevelStats,
+    pub recommended: LevelStats,
+    pub timing: Timing,
+    #[serde(skip)]
+    durations: Vec<(String, Duration)>,
+}
+
+/// Final verdict of a case, after the failure list and the enforcement
+/// policy have been applied.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Passed,
+    /// Passed while the failure list expects it to fail.
+    UnexpectedPass,
+    Failed,
+    /// Failed, but it's a recommended case that isn't enforced.
+    Warning,
+    /// Failed as expected by the failure list.
+    ExpectedFailure,
+    Skipped,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Passed => "pass",
+            Self::UnexpectedPass => "XPASS",
+            Self::Failed => "FAIL",
+            Self::Warning => "warn",
+            Self::ExpectedFailure => "xfail",
+            Self::Skipped => "skip",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Outcome of the cases of a single level.
+#[derive(Serialize, Default)]
+pub struct LevelStats {
+    pub total: u32,
+    pub passed: u32,
+    pub failed: u32,
+    pub percentile: f32,
+}
+
+impl LevelStats {
+    pub fn record(&mut self, passed: bool) {
+        self.total += 1;
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+    fn calculate(&mut self) {
+        self.percentile = if self.total == 0 {
+            100.0
+        } else {
+            (100f32 / self.total as f32) * (self.passed as f32)
+        };
+    }
+    /// Returns how far the score falls short of `min`, if it does.
+    pub fn shortfall(&self, min: f32) -> Option<f32> {
+        if self.percentile < min {
+            Some(min - self.percentile)
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks that a minimum score is a percentage, between 0 and 100.
+pub fn check_score(score: f32) -> anyhow::Result<f32> {
+    if (0.0..=100.0).contains(&score) {
+        Ok(score)
+    } else {
+        Err(anyhow::anyhow!("score {} is not between 0 and 100", score))
+    }
+}
+
+/// Wall-clock timings of the cases, in milliseconds.
+#[derive(Serialize, Default)]
+pub struct Timing {
+    pub total: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
 }
 
 impl Stats {
@@ -19,33 +115,228 @@ impl Stats {
             passed: 0,
             skipped: 0,
             failed: 0,
+            warnings: 0,
+            expected_failures: 0,
+            unexpected_passes: 0,
             percentile: 0.0,
+            required: LevelStats::default(),
+            recommended: LevelStats::default(),
+            timing: Timing::default(),
+            durations: Vec::new(),
         }
     }
     pub fn failed(&self) -> bool {
         return self.failed > 0;
     }
+    pub fn level_mut(&mut self, level: &Level) -> &mut LevelStats {
+        match level {
+            Level::Required => &mut self.required,
+            Level::Recommended => &mut self.recommended,
+        }
+    }
+    pub fn record(&mut self, level: &Level, verdict: Verdict) {
+        match verdict {
+            Verdict::Skipped => self.skipped += 1,
+            Verdict::Passed => self.passed += 1,
+            Verdict::UnexpectedPass => {
+                self.passed += 1;
+                self.unexpected_passes += 1;
+            }
+            Verdict::Failed => self.failed += 1,
+            Verdict::Warning => self.warnings += 1,
+            Verdict::ExpectedFailure => self.expected_failures += 1,
+        }
+        // expected failures still failed, a failure list doesn't make up for
+        // them in the score of their level.
+        if verdict != Verdict::Skipped {
+            let passed = matches!(verdict, Verdict::Passed | Verdict::UnexpectedPass);
+            self.level_mut(level).record(passed);
+        }
+    }
+    pub fn record_duration(&mut self, name: &str, duration: Duration) {
+        self.durations.push((name.to_string(), duration));
+    }
+    pub fn slowest(&self, n: usize) -> Vec<&(String, Duration)> {
+        let mut sorted: Vec<&(String, Duration)> = self.durations.iter().collect();
+        sorted.sort_by_key(|(_, duration)| Reverse(*duration));
+        sorted.truncate(n);
+        sorted
+    }
+    /// Number of cases that ran to completion and were not skipped.
+    pub fn executed(&self) -> u32 {
+        self.passed + self.failed + self.warnings + self.expected_failures
+    }
     pub fn calculate(&mut self) {
-        let total = self.total - self.skipped;
-        self.percentile = (100f32 / total as f32) * (self.passed as f32);
+        let executed = self.executed();
+        self.percentile = if executed == 0 {
+            0.0
+        } else {
+            (100f32 / executed as f32) * (self.passed as f32)
+        };
+        self.required.calculate();
+        self.recommended.calculate();
+        self.timing = Timing::from(&self.durations);
     }
     pub fn json(&self) -> anyhow::Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
     }
 }
 
+impl From<&Vec<(String, Duration)>> for Timing {
+    fn from(durations: &Vec<(String, Duration)>) -> Self {
+        if durations.is_empty() {
+            return Self::default();
+        }
+        let mut millis: Vec<f64> = durations
+            .iter()
+            .map(|(_, d)| d.as_secs_f64() * 1000.0)
+            .collect();
+        millis.sort_by(|a, b| a.total_cmp(b));
+
+        let total: f64 = millis.iter().sum();
+        Self {
+            total,
+            mean: total / millis.len() as f64,
+            p50: percentile(&millis, 50.0),
+            p95: percentile(&millis, 95.0),
+            p99: percentile(&millis, 99.0),
+        }
+    }
+}
+
+/// Nearest-rank percentile `p` (0-100) of `sorted`, which must not be empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let i = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[i.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2}ms total, {:.2}ms mean, {:.2}ms p50, {:.2}ms p95, {:.2}ms p99",
+            self.total, self.mean, self.p50, self.p95, self.p99
+        )
+    }
+}
+
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let formatted = indoc::formatdoc! {"
         {} passed
         {} skipped
-        {} failed \
+        {} failed
+        {} warnings
+        {} expected failures
+        {} unexpected passes
+        {:.2}% required ({}/{})
+        {:.2}% recommended ({}/{})
+        {} \
         ",
             self.passed,
             self.skipped,
             self.failed,
+            self.warnings,
+            self.expected_failures,
+            self.unexpected_passes,
+            self.required.percentile,
+            self.required.passed,
+            self.required.total,
+            self.recommended.percentile,
+            self.recommended.passed,
+            self.recommended.total,
+            self.timing,
 
         };
         write!(f, "{}", formatted)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(millis: &[u64]) -> Timing {
+        let durations: Vec<(String, Duration)> = millis
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| (format!("case{}", i), Duration::from_millis(*ms)))
+            .collect();
+        Timing::from(&durations)
+    }
+
+    #[test]
+    fn no_samples() {
+        let timing = timing(&[]);
+        assert_eq!(timing.total, 0.0);
+        assert_eq!(timing.mean, 0.0);
+        assert_eq!(timing.p50, 0.0);
+        assert_eq!(timing.p99, 0.0);
+    }
+
+    #[test]
+    fn single_sample() {
+        let timing = timing(&[7]);
+        assert_eq!(timing.total, 7.0);
+        assert_eq!(timing.mean, 7.0);
+        assert_eq!(timing.p50, 7.0);
+        assert_eq!(timing.p95, 7.0);
+        assert_eq!(timing.p99, 7.0);
+    }
+
+    #[test]
+    fn nearest_rank() {
+        let sorted: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 10.0), 1.0);
+        assert_eq!(percentile(&sorted, 11.0), 2.0);
+        assert_eq!(percentile(&sorted, 50.0), 5.0);
+        assert_eq!(percentile(&sorted, 95.0), 10.0);
+        assert_eq!(percentile(&sorted, 100.0), 10.0);
+    }
+
+    #[test]
+    fn percentiles_ignore_order() {
+        let timing = timing(&[30, 10, 20, 40]);
+        assert_eq!(timing.total, 100.0);
+        assert_eq!(timing.mean, 25.0);
+        assert_eq!(timing.p50, 20.0);
+        assert_eq!(timing.p99, 40.0);
+    }
+
+    #[test]
+    fn expected_failures_count_against_the_score() {
+        let mut stats = Stats::new(4);
+        stats.record(&Level::Required, Verdict::Passed);
+        stats.record(&Level::Required, Verdict::ExpectedFailure);
+        stats.record(&Level::Required, Verdict::Skipped);
+        stats.record(&Level::Recommended, Verdict::Warning);
+        stats.calculate();
+        assert_eq!(stats.required.total, 2);
+        assert_eq!(stats.required.failed, 1);
+        assert_eq!(stats.required.percentile, 50.0);
+        assert_eq!(stats.required.shortfall(100.0), Some(50.0));
+        assert_eq!(stats.executed(), 3);
+        assert_eq!(stats.recommended.total, 1);
+        assert_eq!(stats.recommended.shortfall(90.0), Some(90.0));
+    }
+
+    #[test]
+    fn scores_are_percentages() {
+        assert_eq!(check_score(0.0).unwrap(), 0.0);
+        assert_eq!(check_score(100.0).unwrap(), 100.0);
+        assert!(check_score(-1.0).is_err());
+        assert!(check_score(100.5).is_err());
+        assert!(check_score(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn slowest_first() {
+        let mut stats = Stats::new(3);
+        stats.record_duration("fast", Duration::from_millis(1));
+        stats.record_duration("slow", Duration::from_millis(30));
+        stats.record_duration("medium", Duration::from_millis(10));
+        let names: Vec<&str> = stats.slowest(2).iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["slow", "medium"]);
+    }
+}
@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::assert::validator::{Check, Validators};
+use crate::custom;
+#[cfg(feature = "extract")]
+use crate::ffi;
+use crate::test_case::{AssertBy, Level, TestCase};
+
+/// Version of the suite file format, bumped on incompatible changes.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SuiteFile {
+    version: u32,
+    cases: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    level: String,
+    syntax: String,
+    payload: String,
+    assertion: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    equivalent: Option<String>,
+    #[serde(default)]
+    require_same_wire_format: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checks: Option<Vec<Check>>,
+}
+
+impl From<&TestCase> for Entry {
+    fn from(case: &TestCase) -> Self {
+        let (equivalent, require_same_wire_format) = match &case.assert_by {
+            AssertBy::Equivalence(equivalent, same_wire) => {
+                (Some(STANDARD.encode(equivalent)), *same_wire)
+            }
+            _ => (None, false),
+        };
+        let checks = match &case.assert_by {
+            AssertBy::Validator(checks) => checks.clone(),
+            _ => None,
+        };
+        Self {
+            name: case.name.clone(),
+            level: case.level.to_string(),
+            syntax: case.syntax.clone(),
+            payload: STANDARD.encode(&case.payload),
+            assertion: case.assert_by.kind().to_string(),
+            equivalent,
+            require_same_wire_format,
+            checks,
+        }
+    }
+}
+
+impl TryFrom<Entry> for TestCase {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: Entry) -> anyhow::Result<Self> {
+        let assert_by = match entry.assertion.as_str() {
+            "equivalence" => {
+                let equivalent = entry
+                    .equivalent
+                    .ok_or(anyhow!("{} has no equivalent message", entry.name))?;
+                AssertBy::Equivalence(
+                    STANDARD.decode(equivalent).with_context(|| {
+                        format!("failed to decode equivalent of {}", entry.name)
+                    })?,
+                    entry.require_same_wire_format,
+                )
+            }
+            "parse_error" => AssertBy::ParseError,
+            "serialize_error" => AssertBy::SerializationError,
+            "validator" => AssertBy::Validator(entry.checks),
+            v => return Err(anyhow!("unknown assertion type {} of {}", v, entry.name)),
+        };
+        Ok(Self {
+            level: Level::try_from(entry.level.as_str())?,
+            syntax: entry.syntax,
+            payload: STANDARD
+                .decode(entry.payload)
+                .with_context(|| format!("failed to decode payload of {}", entry.name))?,
+            name: entry.name,
+            assert_by,
+        })
+    }
+}
+
+/// Suite written by `conform export-suite`, embedded into builds without the
+/// C++ toolchain.
+#[cfg(not(feature = "extract"))]
+const BUNDLED: &str = include_str!("../suite/conformance.json");
+
+/// Extracts the suite from the upstream conformance tests.
+#[cfg(feature = "extract")]
+pub fn extract() -> anyhow::Result<Vec<TestCase>> {
+    let mut binding = ffi::extract_suite();
+    Ok(binding
+        .as_mut()
+        .unwrap()
+        .iter_mut()
+        .map(|mut raw| TestCase::from(&mut raw))
+        .collect())
+}
+
+/// Loads the suite embedded at build time.
+#[cfg(not(feature = "extract"))]
+pub fn extract() -> anyhow::Result<Vec<TestCase>> {
+    from_json(BUNDLED).context("failed to load the bundled suite")
+}
+
+pub fn to_json(cases: &[TestCase]) -> anyhow::Result<String> {
+    let file = SuiteFile {
+        version: VERSION,
+        cases: cases.iter().map(Entry::from).collect(),
+    };
+    serde_json::to_string(&file).context("failed to serialize the suite")
+}
+
+pub fn from_json(json: &str) -> anyhow::Result<Vec<TestCase>> {
+    let file: SuiteFile = serde_json::from_str(json).context("failed to parse the suite")?;
+    if file.version != VERSION {
+        return Err(anyhow!(
+            "suite file is version {}, only version {} is supported",
+            file.version,
+            VERSION
+        ));
+    }
+    file.cases.into_iter().map(TestCase::try_from).collect()
+}
+
+pub fn write(path: &str, cases: &[TestCase]) -> anyhow::Result<()> {
+    fs::write(path, to_json(cases)?).with_context(|| format!("failed to write {}", path))
+}
+
+pub fn read(path: &str) -> anyhow::Result<Vec<TestCase>> {
+    let json = fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?;
+    from_json(&json).with_context(|| format!("failed to load suite {}", path))
+}
+
+/// Where the cases come from. Without any of them, this is the upstream
+/// suite with the built-in validators.
+#[derive(Default)]
+pub struct Sources<'a> {
+    /// Suite file to use instead of the upstream suite.
+    pub suite: Option<&'a str>,
+    /// Directory of custom cases to add to the suite.
+    pub cases_dir: Option<&'a str>,
+    /// File of validators overriding the built-in ones.
+    pub validators: Option<&'a str>,
+}
+
+/// Loads the cases to run from `sources`.
+pub fn load(sources: &Sources) -> anyhow::Result<Vec<TestCase>> {
+    let mut cases = match sources.suite {
+        Some(path) => read(path)?,
+        None => extract()?,
+    };
+    if let Some(dir) = sources.cases_dir {
+        for case in custom::load_dir(dir)? {
+            if cases.iter().any(|c| c.name == case.name) {
+                return Err(anyhow!(
+                    "custom case {} is already part of the suite",
+                    case.name
+                ));
+            }
+            cases.push(case);
+        }
+    }
+    let overrides = match sources.validators {
+        Some(path) => Validators::load(path)?,
+        None => Validators::default(),
+    };
+    Validators::builtin().apply(&overrides, &mut cases);
+    Ok(cases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert::validator::JsonType;
+
+    fn case(name: &str, level: Level, assert_by: AssertBy) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            level,
+            syntax: String::from("proto3"),
+            payload: vec![0, 1, 2, 0xff],
+            assert_by,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let checks = vec![Check {
+            pointer: String::from("/optionalInt32"),
+            present: Some(true),
+            kind: Some(JsonType::Integer),
+            equals: None,
+            regex: None,
+        }];
+        let cases = vec![
+            case(
+                "Required.A",
+                Level::Required,
+                AssertBy::Equivalence(vec![8, 1], true),
+            ),
+            case("Recommended.B", Level::Recommended, AssertBy::ParseError),
+            case("Required.C", Level::Required, AssertBy::SerializationError),
+            case(
+                "Required.D",
+                Level::Required,
+                AssertBy::Validator(Some(checks)),
+            ),
+            case("Required.E", Level::Required, AssertBy::Validator(None)),
+        ];
+
+        let loaded = from_json(&to_json(&cases).unwrap()).unwrap();
+        assert_eq!(loaded.len(), cases.len());
+        for (loaded, case) in loaded.iter().zip(&cases) {
+            assert_eq!(loaded.name, case.name);
+            assert!(loaded.level == case.level);
+            assert_eq!(loaded.syntax, case.syntax);
+            assert_eq!(loaded.payload, case.payload);
+            assert!(loaded.assert_by == case.assert_by, "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let json = format!(r#"{{"version": {}, "cases": []}}"#, VERSION + 1);
+        let err = from_json(&json).err().unwrap();
+        assert!(
+            err.to_string().contains("only version 1 is supported"),
+            "{}",
+            err
+        );
+        assert!(from_json(r#"{"cases": []}"#).is_err());
+        assert!(
+            from_json(&format!(r#"{{"version": {}, "cases": []}}"#, VERSION))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn broken_entries_are_rejected() {
+        let entry = |assertion: &str, payload: &str| {
+            format!(
+                r#"{{"version": 1, "cases": [{{"name": "Required.A", "level": "Required", "syntax": "proto3", "payload": "{}", "assertion": "{}"}}]}}"#,
+                payload, assertion
+            )
+        };
+        assert!(from_json(&entry("parse_error", "AAEC")).is_ok());
+        assert!(from_json(&entry("equivalence", "AAEC")).is_err());
+        assert!(from_json(&entry("guess", "AAEC")).is_err());
+        assert!(from_json(&entry("parse_error", "not base64!")).is_err());
+    }
+}
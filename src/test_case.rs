@@ -1,9 +1,16 @@
+use crate::assert::conformance::{conformance_request::Payload, ConformanceRequest, WireFormat};
+use crate::assert::validator::Check;
+#[cfg(feature = "extract")]
 use crate::ffi;
+use anyhow::Context;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use core::fmt;
-use std::{fmt::Formatter, pin::Pin};
+use protobuf::Message;
+use std::fmt::Formatter;
+#[cfg(feature = "extract")]
+use std::pin::Pin;
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone)]
 pub enum Level {
     Required,
     Recommended,
@@ -19,6 +26,18 @@ impl From<i32> for Level {
     }
 }
 
+impl TryFrom<&str> for Level {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "Required" => Ok(Self::Required),
+            "Recommended" => Ok(Self::Recommended),
+            _ => Err(anyhow::anyhow!("unknown level {}", value)),
+        }
+    }
+}
+
 impl ToString for Level {
     fn to_string(&self) -> String {
         match self {
@@ -28,14 +47,28 @@ impl ToString for Level {
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone)]
 pub enum AssertBy {
     Equivalence(Vec<u8>, bool),
     ParseError,
     SerializationError,
-    Validator,
+    /// Validates the json response, with the built-in validator of the case
+    /// unless it has checks of its own.
+    Validator(Option<Vec<Check>>),
+}
+
+impl AssertBy {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Equivalence(_, _) => "equivalence",
+            Self::ParseError => "parse_error",
+            Self::SerializationError => "serialize_error",
+            Self::Validator(_) => "validator",
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct TestCase {
     pub name: String,
     pub level: Level,
@@ -44,6 +77,9 @@ pub struct TestCase {
     pub assert_by: AssertBy,
 }
 
+/// Names of the wire formats, the way `--skip-format` accepts them.
+pub const FORMATS: [&str; 4] = ["protobuf", "json", "jspb", "text"];
+
 impl TestCase {
     pub fn is_recommended(&self) -> bool {
         self.level == Level::Recommended
@@ -52,8 +88,34 @@ impl TestCase {
     pub fn is_required(&self) -> bool {
         self.level == Level::Required
     }
+
+    pub fn request(&self) -> anyhow::Result<ConformanceRequest> {
+        ConformanceRequest::parse_from_bytes(&self.payload).context("failed to parse request")
+    }
+
+    /// Input and requested output formats of the case, named as in
+    /// [`FORMATS`].
+    pub fn formats(&self) -> anyhow::Result<Vec<&'static str>> {
+        let request = self.request()?;
+        let input = match request.payload {
+            Some(Payload::ProtobufPayload(_)) => Some("protobuf"),
+            Some(Payload::JsonPayload(_)) => Some("json"),
+            Some(Payload::JspbPayload(_)) => Some("jspb"),
+            Some(Payload::TextPayload(_)) => Some("text"),
+            None => None,
+        };
+        let output = match request.requested_output_format.enum_value() {
+            Ok(WireFormat::PROTOBUF) => Some("protobuf"),
+            Ok(WireFormat::JSON) => Some("json"),
+            Ok(WireFormat::JSPB) => Some("jspb"),
+            Ok(WireFormat::TEXT_FORMAT) => Some("text"),
+            _ => None,
+        };
+        Ok(input.into_iter().chain(output).collect())
+    }
 }
 
+#[cfg(feature = "extract")]
 impl From<&mut Pin<&mut ffi::Case>> for TestCase {
     fn from(value: &mut Pin<&mut ffi::Case>) -> Self {
         let assert_by = match value.as_mut().get_assert_by().to_string().as_str() {
@@ -63,7 +125,7 @@ impl From<&mut Pin<&mut ffi::Case>> for TestCase {
             ),
             "f_parse" => AssertBy::ParseError,
             "f_serialize" => AssertBy::SerializationError,
-            "json_validator" => AssertBy::Validator,
+            "json_validator" => AssertBy::Validator(None),
             v => panic!("unknown assertion type {}", &v)
          };
         Self {
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+
+/// Where requests are written to the testee.
+pub type Input = Box<dyn Write + Send>;
+/// Where responses of the testee are read from.
+pub type Output = Box<dyn Read + Send>;
+
+/// How the length-prefixed requests and responses travel between conform and
+/// the testee.
+pub trait Transport: Send {
+    /// Starts or connects to the testee.
+    fn open(&mut self) -> anyhow::Result<(Input, Output)>;
+
+    /// Stops the testee, or disconnects from it.
+    fn close(&mut self) -> io::Result<()>;
+
+    /// The command that starts the testee, for transports that spawn it.
+    fn command(&mut self) -> Option<&mut Command> {
+        None
+    }
+
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Parses the address of a testee: `unix:<path>` connects to a unix socket,
+/// `tcp:<host>:<port>` to a tcp port and anything else is a program to spawn
+/// and talk to over stdin and stdout.
+pub fn from_address(address: &str) -> Box<dyn Transport> {
+    if let Some(path) = address.strip_prefix("unix:") {
+        Box::new(Socket::new(Address::Unix(path.to_string())))
+    } else if let Some(addr) = address.strip_prefix("tcp:") {
+        Box::new(Socket::new(Address::Tcp(addr.to_string())))
+    } else {
+        Box::new(Process::new(address))
+    }
+}
+
+/// A program spawned for the run, speaking over its stdin and stdout.
+pub struct Process {
+    command: Command,
+    child: Option<Child>,
+}
+
+impl Process {
+    pub fn new(program: &str) -> Self {
+        Self {
+            command: Command::new(program),
+            child: None,
+        }
+    }
+}
+
+impl Transport for Process {
+    fn open(&mut self) -> anyhow::Result<(Input, Output)> {
+        if self.child.is_some() {
+            return Err(anyhow!("program is already running"));
+        }
+
+        let mut child = self
+            .command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| "failed to spawn the testee program")?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        self.child = Some(child);
+        Ok((Box::new(stdin), Box::new(stdout)))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        match self.child.as_mut() {
+            Some(child) => child.kill(),
+            None => Ok(()),
+        }
+    }
+
+    fn command(&mut self) -> Option<&mut Command> {
+        Some(&mut self.command)
+    }
+
+    fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|c| c.id())
+    }
+}
+
+/// A testee that is already running, such as inside a long-lived server,
+/// and listens on a socket.
+pub struct Socket {
+    address: Address,
+    connection: Option<Connection>,
+}
+
+pub enum Address {
+    /// Path of a unix domain socket.
+    Unix(String),
+    /// `host:port` of a tcp listener.
+    Tcp(String),
+}
+
+enum Connection {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Socket {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            connection: None,
+        }
+    }
+
+    fn connect(&self) -> anyhow::Result<Connection> {
+        match &self.address {
+            #[cfg(unix)]
+            Address::Unix(path) => UnixStream::connect(path)
+                .map(Connection::Unix)
+                .with_context(|| format!("failed to connect to unix socket {}", path)),
+            #[cfg(not(unix))]
+            Address::Unix(_) => Err(anyhow!("unix sockets are not supported on this platform")),
+            Address::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)
+                    .with_context(|| format!("failed to connect to {}", addr))?;
+                // requests are small and answered one at a time
+                stream
+                    .set_nodelay(true)
+                    .context("failed to set TCP_NODELAY")?;
+                Ok(Connection::Tcp(stream))
+            }
+        }
+    }
+}
+
+impl Transport for Socket {
+    fn open(&mut self) -> anyhow::Result<(Input, Output)> {
+        if self.connection.is_some() {
+            return Err(anyhow!("already connected to the testee"));
+        }
+
+        let connection = self.connect()?;
+        let clone = || -> io::Result<(Input, Output)> {
+            Ok(match &connection {
+                #[cfg(unix)]
+                Connection::Unix(stream) => {
+                    (Box::new(stream.try_clone()?), Box::new(stream.try_clone()?))
+                }
+                Connection::Tcp(stream) => {
+                    (Box::new(stream.try_clone()?), Box::new(stream.try_clone()?))
+                }
+            })
+        };
+        let (input, output) = clone().context("failed to clone the connection")?;
+        self.connection = Some(connection);
+        Ok((input, output))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        // shutting down also ends the read of a pending response
+        let result = match self.connection.take() {
+            #[cfg(unix)]
+            Some(Connection::Unix(stream)) => stream.shutdown(Shutdown::Both),
+            Some(Connection::Tcp(stream)) => stream.shutdown(Shutdown::Both),
+            None => Ok(()),
+        };
+        match result {
+            // the testee hung up first, such as when it crashed
+            Err(err) if err.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    #[cfg(unix)]
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Answers one frame with the same bytes, then hangs up.
+    fn echo(mut stream: impl Read + Write) {
+        let mut frame = [0u8; 8];
+        stream.read_exact(&mut frame).unwrap();
+        stream.write_all(&frame).unwrap();
+    }
+
+    fn round_trip(transport: &mut dyn Transport) {
+        let (mut input, mut output) = transport.open().unwrap();
+        input.write_all(b"\x04\0\0\0ping").unwrap();
+        let mut frame = [0u8; 8];
+        output.read_exact(&mut frame).unwrap();
+        assert_eq!(&frame, b"\x04\0\0\0ping");
+    }
+
+    #[test]
+    fn addresses() {
+        assert!(from_address("unix:/tmp/testee.sock").command().is_none());
+        assert!(from_address("tcp:localhost:9000").command().is_none());
+        let mut process = from_address("runners/ts");
+        assert_eq!(process.command().unwrap().get_program(), "runners/ts");
+        // a colon alone doesn't make an address
+        assert!(from_address("C:/runners/ts").command().is_some());
+    }
+
+    #[test]
+    fn tcp_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("tcp:{}", listener.local_addr().unwrap());
+        let peer = thread::spawn(move || echo(listener.accept().unwrap().0));
+
+        let mut transport = from_address(&address);
+        round_trip(transport.as_mut());
+        assert!(transport.open().is_err());
+        peer.join().unwrap();
+        // the peer is gone by now
+        transport.close().unwrap();
+        transport.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_round_trip() {
+        let path = std::env::temp_dir().join(format!("conform-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let peer = thread::spawn(move || echo(listener.accept().unwrap().0));
+
+        let mut transport = from_address(&format!("unix:{}", path.display()));
+        round_trip(transport.as_mut());
+        peer.join().unwrap();
+        transport.close().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nobody_listening() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let err = from_address(&format!("tcp:{}", addr)).open().err().unwrap();
+        assert_eq!(err.to_string(), format!("failed to connect to {}", addr));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_round_trip() {
+        let mut transport = from_address("cat");
+        round_trip(transport.as_mut());
+        assert!(transport.pid().is_some());
+        assert!(transport.open().is_err());
+        transport.close().unwrap();
+    }
+
+    #[test]
+    fn closing_before_opening() {
+        assert!(from_address("tcp:localhost:9000").close().is_ok());
+        assert!(from_address("runners/ts").close().is_ok());
+    }
+}